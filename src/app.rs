@@ -0,0 +1,428 @@
+use std::fs;
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread::{self, JoinHandle};
+use std::time::Instant;
+
+use chrono::Duration;
+use timer::{Guard, MessageTimer};
+
+use crate::board::{Board, BoardEvevents, SimpleBoard};
+use crate::error::FlowError;
+use crate::game::{self, GameConfig};
+use crate::game_over::GameOver;
+use crate::level::Level;
+use crate::map::SimpleMap;
+use crate::notification::BoardNotification;
+use crate::renderer::{FrontendEvent, Input, Menu, Renderer};
+use crate::snapshot::BoardSnapshot;
+use crate::state_machine::{Hierarchy, Response, StateMachine};
+use crate::utils::Direction;
+
+const LEVELS_DIRECTORY: &str = "levels";
+const QUICKSAVE_FILE: &str = "quicksave.sav";
+const TITLE_ENTRIES: [&str; 3] = ["Play", "Select level", "Quit"];
+const PAUSE_ENTRIES: [&str; 3] = ["Resume", "Restart", "Main menu"];
+const GAME_OVER_ENTRIES: [&str; 2] = ["Play again", "Main menu"];
+
+// A game is running while in `InGame`, its ticks are only produced while `Playing`
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum AppState {
+    TitleMenu,
+    LevelSelect,
+    InGame,
+    Playing,
+    Paused,
+    GameOver,
+}
+
+struct LevelEntry {
+    name: String,
+    // A generated map is played when there is no level file
+    path: Option<String>,
+}
+
+// The board runs on a thread of its own. The application only sends it events and reads the
+// snapshots and notifications it publishes.
+struct Game {
+    board_thread: JoinHandle<Result<GameOver, FlowError>>,
+    events_sender: mpsc::Sender<BoardEvevents>,
+    snapshots: mpsc::Receiver<BoardSnapshot>,
+    notifications: mpsc::Receiver<BoardNotification>,
+    // Latest snapshot published by the board
+    snapshot: BoardSnapshot,
+    seed: u64,
+    move_heads_period_ms: i64,
+    slide_frame_period_ms: i64,
+    // Slide count of the board and the moment it last changed, to animate the scrolling
+    last_slide: (u64, Instant),
+}
+
+impl Game {
+    // Closing the events channel stops the board if the game is still going on
+    fn stop(self) -> Result<(), String> {
+        drop(self.events_sender);
+        match self.board_thread.join() {
+            Ok(Ok(_)) | Ok(Err(FlowError::ChannelClosed)) => Ok(()),
+            Ok(Err(error)) => Err(format!("The board stopped: {}", error)),
+            Err(_) => Err(String::from("Board thread panicked")),
+        }
+    }
+}
+
+// Tick producers. They keep firing as long as their guards are alive
+struct Ticks {
+    _move_heads_guard: Guard,
+    _slide_frame_guard: Guard,
+    _move_heads_timer: MessageTimer<BoardEvevents>,
+    _slide_frame_timer: MessageTimer<BoardEvevents>,
+}
+
+impl Ticks {
+    fn start(game: &Game) -> Self {
+        let move_heads_timer = MessageTimer::new(game.events_sender.clone());
+        let move_heads_guard = move_heads_timer.schedule_repeating(
+            Duration::milliseconds(game.move_heads_period_ms),
+            BoardEvevents::MOVE_HEADS_TICK,
+        );
+
+        let slide_frame_timer = MessageTimer::new(game.events_sender.clone());
+        let slide_frame_guard = slide_frame_timer.schedule_repeating(
+            Duration::milliseconds(game.slide_frame_period_ms),
+            BoardEvevents::SLIDE_FRAME_TICK,
+        );
+
+        Ticks {
+            _move_heads_guard: move_heads_guard,
+            _slide_frame_guard: slide_frame_guard,
+            _move_heads_timer: move_heads_timer,
+            _slide_frame_timer: slide_frame_timer,
+        }
+    }
+}
+
+// Application around the board: menus, level selection, pause and game over screens
+pub struct App {
+    config: GameConfig,
+    renderer: Box<dyn Renderer>,
+    state: AppState,
+    levels: Vec<LevelEntry>,
+    // Level played by the next game
+    level: Option<String>,
+    // Highlighted entry of the current menu
+    selected: usize,
+    game: Option<Game>,
+    ticks: Option<Ticks>,
+    // Outcome and seed of the last finished game
+    last_game_over: Option<(GameOver, u64)>,
+    quit: bool,
+}
+
+impl App {
+    pub fn new(config: GameConfig, renderer: Box<dyn Renderer>) -> Self {
+        let levels = find_levels(LEVELS_DIRECTORY, config.level.as_deref());
+        App {
+            level: config.level.clone(),
+            config,
+            renderer,
+            state: AppState::TitleMenu,
+            levels,
+            selected: 0,
+            game: None,
+            ticks: None,
+            last_game_over: None,
+            quit: false,
+        }
+    }
+
+    // Run until the player quits. Returns the outcome and seed of the last finished game
+    pub fn run(mut self) -> Result<Option<(GameOver, u64)>, String> {
+        self.init()?;
+        while !self.quit {
+            match self.renderer.next_event() {
+                Some(event) => self.process(event)?,
+                None => break,
+            }
+        }
+        Ok(self.last_game_over)
+    }
+
+    fn start_game(&self) -> Result<Game, String> {
+        let config = GameConfig {
+            map_size: if self.level.is_some() { None } else { self.config.map_size },
+            level: self.level.clone(),
+            ..self.config.clone()
+        };
+        let (header, board_config) = game::load_board_config(&config)?;
+        let recorder = game::open_recorder(&config, &board_config)?;
+        let seed = board_config.seed;
+
+        let (events_sender, events_receiver) = mpsc::channel();
+        let mut board = SimpleBoard::<SimpleMap>::new(events_receiver, board_config);
+        if let Some(recorder) = recorder {
+            board.record_events(recorder);
+        }
+        let (snapshot_sender, snapshots) = mpsc::channel();
+        board.publish_snapshots(snapshot_sender);
+        let (notification_sender, notifications) = mpsc::channel();
+        board.subscribe(Box::new(notification_sender));
+        let board_thread = thread::spawn(move || board.run());
+
+        Ok(Game {
+            board_thread,
+            events_sender,
+            snapshots,
+            notifications,
+            snapshot: BoardSnapshot::default(),
+            seed,
+            move_heads_period_ms: game::move_heads_period_ms(&config, &header),
+            slide_frame_period_ms: game::slide_frame_period_ms(&config, &header),
+            last_slide: (0, Instant::now()),
+        })
+    }
+
+    fn menu(&self, state: AppState) -> Option<Menu> {
+        let (title, entries): (String, Vec<String>) = match state {
+            AppState::TitleMenu => (game::DEFAULT_TITLE.to_string(), TITLE_ENTRIES.map(String::from).to_vec()),
+            AppState::LevelSelect => {
+                (String::from("Select level"), self.levels.iter().map(|level| level.name.clone()).collect())
+            }
+            AppState::Paused => (String::from("Paused"), PAUSE_ENTRIES.map(String::from).to_vec()),
+            AppState::GameOver => {
+                let title = match &self.last_game_over {
+                    Some((game_over, seed)) => {
+                        format!("{} - score {} - seed {}", game_over.headline(), game_over.score.total(), seed)
+                    }
+                    None => String::from("Game over"),
+                };
+                (title, GAME_OVER_ENTRIES.map(String::from).to_vec())
+            }
+            AppState::InGame | AppState::Playing => return None,
+        };
+
+        Some(Menu {
+            title,
+            entries,
+            selected: self.selected,
+        })
+    }
+
+    fn render_handler(&mut self, state: AppState) -> Result<Response<AppState, ()>, String> {
+        if let Some(menu) = self.menu(state) {
+            self.renderer.draw_menu(&menu);
+            return Ok(Response::Handled(()));
+        }
+
+        let game = match &mut self.game {
+            Some(game) if state == AppState::Playing => game,
+            _ => return Ok(Response::Super),
+        };
+
+        for notification in game.notifications.try_iter() {
+            match notification {
+                BoardNotification::GAME_OVER(game_over) => {
+                    self.last_game_over = Some((game_over, game.seed));
+                    return Ok(Response::Transition(AppState::GameOver, ()));
+                }
+                BoardNotification::BOARD_FAILED(error) => self.renderer.set_message(Some(error.to_string())),
+                _ => (),
+            }
+        }
+        // The board only stops by itself once the game is over
+        if game.board_thread.is_finished() {
+            return Err(String::from("The board stopped before the game was over"));
+        }
+
+        // Only the latest snapshot is drawn, the ones in between are skipped
+        if let Some(snapshot) = game.snapshots.try_iter().last() {
+            game.snapshot = snapshot;
+        }
+        if game.snapshot.slide_count != game.last_slide.0 {
+            game.last_slide = (game.snapshot.slide_count, Instant::now());
+        }
+        let elapsed_ms = game.last_slide.1.elapsed().as_millis() as f64;
+        let scroll = (elapsed_ms / game.slide_frame_period_ms as f64).min(1.0);
+
+        self.renderer.draw(&game.snapshot, scroll);
+        Ok(Response::Handled(()))
+    }
+
+    fn playing_input_handler(&mut self, input: Input) -> Result<Response<AppState, ()>, String> {
+        let event = match input {
+            Input::SetDirection(direction) => BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction: Some(direction) },
+            Input::ReleaseDirection => BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction: None },
+            Input::CycleFocus => BoardEvevents::FOCUS_NEXT_HEAD,
+            Input::QuickSave => BoardEvevents::SAVE_GAME { path: String::from(QUICKSAVE_FILE) },
+            Input::QuickLoad => BoardEvevents::LOAD_GAME { path: String::from(QUICKSAVE_FILE) },
+            Input::Pause | Input::Back => return Ok(Response::Transition(AppState::Paused, ())),
+            Input::Confirm => return Ok(Response::Handled(())),
+        };
+
+        if let Some(game) = &self.game {
+            game.events_sender
+                .send(event)
+                .map_err(|_| String::from("The board stopped receiving events"))?;
+        }
+        Ok(Response::Handled(()))
+    }
+
+    fn menu_input_handler(&mut self, state: AppState, input: Input) -> Result<Response<AppState, ()>, String> {
+        let entries_count = self.menu(state).map_or(0, |menu| menu.entries.len());
+        if entries_count == 0 {
+            return Ok(Response::Super);
+        }
+
+        let target = match input {
+            Input::SetDirection(Direction::Up) => {
+                self.selected = (self.selected + entries_count - 1) % entries_count;
+                return Ok(Response::Handled(()));
+            }
+            Input::SetDirection(Direction::Down) => {
+                self.selected = (self.selected + 1) % entries_count;
+                return Ok(Response::Handled(()));
+            }
+            Input::Confirm => self.menu_choice(state, self.selected),
+            Input::Back | Input::Pause => match state {
+                AppState::Paused => Some(AppState::Playing),
+                AppState::LevelSelect | AppState::GameOver => Some(AppState::TitleMenu),
+                _ => {
+                    self.quit = true;
+                    None
+                }
+            },
+            Input::SetDirection(_) | Input::ReleaseDirection | Input::CycleFocus | Input::QuickSave | Input::QuickLoad => None,
+        };
+
+        match target {
+            Some(target) => Ok(Response::Transition(target, ())),
+            None => Ok(Response::Handled(())),
+        }
+    }
+
+    // State to go to when the `index` entry of the menu is chosen
+    fn menu_choice(&mut self, state: AppState, index: usize) -> Option<AppState> {
+        match (state, index) {
+            (AppState::TitleMenu, 0) => Some(AppState::InGame),
+            (AppState::TitleMenu, 1) => Some(AppState::LevelSelect),
+            (AppState::TitleMenu, _) => {
+                self.quit = true;
+                None
+            }
+            (AppState::LevelSelect, index) => {
+                self.level = self.levels.get(index).and_then(|level| level.path.clone());
+                Some(AppState::InGame)
+            }
+            (AppState::Paused, 0) => Some(AppState::Playing),
+            // Going back to `InGame` leaves it first, which ends the current game
+            (AppState::Paused, 1) | (AppState::GameOver, 0) => Some(AppState::InGame),
+            (AppState::Paused, _) | (AppState::GameOver, _) => Some(AppState::TitleMenu),
+            (AppState::InGame, _) | (AppState::Playing, _) => None,
+        }
+    }
+}
+
+impl Hierarchy for App {
+    type State = AppState;
+    type Error = String;
+
+    fn state(&self) -> AppState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: AppState) {
+        self.state = state;
+    }
+
+    fn parent(state: AppState) -> Option<AppState> {
+        match state {
+            AppState::Playing | AppState::Paused => Some(AppState::InGame),
+            _ => None,
+        }
+    }
+
+    fn initial(state: AppState) -> Option<AppState> {
+        match state {
+            AppState::InGame => Some(AppState::Playing),
+            _ => None,
+        }
+    }
+
+    fn entry(&mut self, state: AppState) -> Result<(), String> {
+        match state {
+            AppState::InGame => {
+                self.game = Some(self.start_game()?);
+                self.renderer.set_message(None);
+            }
+            AppState::Playing => {
+                if let Some(game) = &mut self.game {
+                    self.ticks = Some(Ticks::start(game));
+                    // The next slide comes a whole period after the ticks start again
+                    game.last_slide.1 = Instant::now();
+                }
+            }
+            _ => self.selected = 0,
+        }
+        Ok(())
+    }
+
+    fn exit(&mut self, state: AppState) -> Result<(), String> {
+        match state {
+            AppState::InGame => {
+                if let Some(game) = self.game.take() {
+                    game.stop()?;
+                }
+            }
+            AppState::Playing => self.ticks = None,
+            _ => (),
+        }
+        Ok(())
+    }
+}
+
+impl StateMachine<FrontendEvent> for App {
+    type Output = ();
+
+    fn handle(&mut self, state: AppState, event: &mut FrontendEvent) -> Result<Response<AppState, ()>, String> {
+        match (state, *event) {
+            (_, FrontendEvent::Render) => self.render_handler(state),
+            (AppState::Playing, FrontendEvent::Input(input)) => self.playing_input_handler(input),
+            (AppState::InGame, FrontendEvent::Input(_)) => Ok(Response::Handled(())),
+            (_, FrontendEvent::Input(input)) => self.menu_input_handler(state, input),
+        }
+    }
+}
+
+// Levels of `directory`, sorted by file name, after the generated map and the level given on the command line
+fn find_levels(directory: &str, command_line_level: Option<&str>) -> Vec<LevelEntry> {
+    let mut paths: Vec<String> = fs::read_dir(directory)
+        .map(|entries| {
+            entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.path())
+                .filter(|path| path.extension().is_some_and(|extension| extension == "lvl"))
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    paths.sort();
+    if let Some(level) = command_line_level {
+        if !paths.iter().any(|path| Path::new(path) == Path::new(level)) {
+            paths.insert(0, level.to_string());
+        }
+    }
+
+    let mut levels = vec![LevelEntry {
+        name: String::from("Generated map"),
+        path: None,
+    }];
+    levels.extend(paths.into_iter().map(|path| {
+        // Levels that cannot be read are still listed, the error shows up when playing them
+        let title = Level::from_file(&path).ok().and_then(|level| level.header.title);
+        let name = title.unwrap_or_else(|| {
+            let stem = Path::new(&path).file_stem().map(|stem| stem.to_string_lossy().into_owned());
+            stem.unwrap_or_else(|| path.clone())
+        });
+        LevelEntry { name, path: Some(path) }
+    }));
+    levels
+}
@@ -1,15 +1,37 @@
 use crate::head_list::HeadList;
-use crate::heads::{self, Head, HeadAction, HeadEvents, SimpleHead};
-use crate::map::{Map, TileType};
+use crate::heads::{self, Head, HeadEvents, HeadOutcome, HeadState, SimpleHead};
+use crate::map::{Map, MapConfig};
 use crate::utils::{Coordinates, Direction, DirectionFlags};
+use std::collections::BTreeMap;
+use std::convert::Infallible;
 use std::sync::mpsc::{Receiver};
-use crate::mpsc::Sender;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::collision::{CollisionRule, TrailMark, TrailRule};
+use crate::direction_picker::DirectionPicker;
+use crate::direction_strategy::StrategyKind;
+use crate::error::{BoardError, FlowError};
+use crate::game_over::{EndConditions, GameOver, GameOverReason};
+use crate::level::LevelHeader;
+use crate::map::TileType;
+use crate::notification::{BoardNotification, BoardObserver, KillCause};
+use crate::replay::Recorder;
+use crate::save::{RngState, SavableMap, SavedGame};
+use crate::score::Score;
+use crate::state_machine::{Hierarchy, Response, StateMachine};
+use crate::snapshot::{BoardSnapshot, SnapshotSender};
+
+// Marks made during this many move ticks before the current one are still fresh: a head can
+// merge into the head of its lineage that made them
+const FRESH_MARK_TICKS: u64 = 2;
 
 #[derive(Debug, Clone)]
 pub enum BoardEvevents {
     SLIDE_FRAME_TICK,
+    // Heads die and split within the tick that caused it, these two are left to scripts
     KILL_HEAD {
         id: heads::Id,
+        cause: KillCause,
     },
     ADD_HEAD {
         position: Coordinates,
@@ -17,17 +39,69 @@ pub enum BoardEvevents {
         parent_direction: Direction,
     },
     MOVE_HEADS_TICK,
+    // Steer the focused head, or every head without a direction of its own when none is focused
     SET_NEXT_HEAD_DIRECTION {
         direction: Option<Direction>,
     },
+    FOCUS_HEAD {
+        id: Option<heads::Id>,
+    },
+    // Focus the head with the next id, then none after the last one
+    FOCUS_NEXT_HEAD,
+    SAVE_GAME {
+        path: String,
+    },
+    LOAD_GAME {
+        path: String,
+    },
+}
+
+pub struct BoardConfig {
+    pub map: MapConfig,
+    pub seed: u64,
+    // How heads pick a direction when the player does not set one
+    pub strategy: StrategyKind,
+    // Settles the heads moving to the same tile
+    pub collision_rule: CollisionRule,
+    // Settles the heads moving to a marked tile
+    pub trail_rule: TrailRule,
+    pub end_conditions: EndConditions,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum BoardState {
+    Running,
+    // Events received once the game is over are dropped
+    Over(GameOverReason),
 }
 
 pub struct SimpleBoard<MapType: Map> {
     map: MapType,
     heads: HeadList<SimpleHead>,
     events_receiver: Receiver<BoardEvevents>,
-    events_sender: Sender<BoardEvevents>,
+    // Direction of the heads the player did not steer one by one
     next_direction: Option<Direction>,
+    // Head the player is steering
+    focused_head: Option<heads::Id>,
+    head_directions: BTreeMap<heads::Id, Direction>,
+    // Seed the game was started with
+    seed: u64,
+    // Draws the seeds of the direction pickers given to new heads
+    rng: ChaCha8Rng,
+    strategy: StrategyKind,
+    collision_rule: CollisionRule,
+    trail_rule: TrailRule,
+    // Tiles marked during the last move ticks, from the oldest to the latest
+    marks: Vec<TrailMark>,
+    slide_count: u64,
+    tick_count: u64,
+    score: Score,
+    end_conditions: EndConditions,
+    state: BoardState,
+    // Where a fresh snapshot is published after each processed event
+    snapshot_sender: Option<SnapshotSender>,
+    observers: Vec<Box<dyn BoardObserver>>,
+    recorder: Option<Recorder>,
 }
 
 mod private {
@@ -35,77 +109,920 @@ mod private {
 
     pub trait Sealed {
         fn move_heads_handler(&mut self, direction: Option<Direction>);
-        fn kill_head_handler(&mut self, id: heads::Id);
+        fn set_direction_handler(&mut self, direction: Option<Direction>);
+        fn focus_head_handler(&mut self, id: Option<heads::Id>);
+        fn focus_next_head_handler(&mut self);
+        fn kill_head_handler(&mut self, id: heads::Id, cause: KillCause);
+        fn merge_head_handler(&mut self, id: heads::Id, into: heads::Id);
+        fn deliver_head_handler(&mut self, id: heads::Id);
+        fn slide_frame_handler(&mut self);
+        fn add_head_handler(&mut self, position: Coordinates, coming_from: Direction, parent_direction: Direction, lineage: Option<u64>) -> Vec<(heads::Id, HeadOutcome)>;
+        fn apply_outcomes(&mut self, outcomes: Vec<(heads::Id, HeadOutcome)>);
+        fn resolve_collisions(&mut self, outcomes: Vec<(heads::Id, HeadOutcome)>, merges: &mut Vec<(heads::Id, heads::Id)>) -> Vec<(heads::Id, HeadOutcome)>;
+        fn merge_target(&self, id: heads::Id, position: Coordinates) -> Option<heads::Id>;
+        fn save_game_handler(&mut self, path: &str);
+        fn load_game_handler(&mut self, path: &str);
+        fn dispatch(&mut self, evt: BoardEvevents);
+        fn end_condition_met(&self) -> Option<GameOverReason>;
     }
 }
 pub trait Board: private::Sealed {
-    fn new(events_sender: Sender<BoardEvevents>, events_receiver: Receiver<BoardEvevents>);
-    fn run(&mut self);
+    fn new(events_receiver: Receiver<BoardEvevents>, config: BoardConfig) -> Self;
+    // Process events until the game is over
+    fn run(&mut self) -> Result<GameOver, FlowError>;
 }
 
-impl<MapType: Map> private::Sealed for SimpleBoard<MapType> {
+impl<MapType: SavableMap> private::Sealed for SimpleBoard<MapType> {
     fn move_heads_handler(&mut self, direction: Option<Direction>) {
         let map = &mut self.map;
+        let head_directions = &self.head_directions;
+        let observers = &mut self.observers;
+        let mut outcomes = Vec::new();
 
+        self.tick_count += 1;
+        self.score.move_tick(self.heads.iter().count());
+        let tick_count = self.tick_count;
+        self.marks.retain(|mark| tick_count - mark.tick <= FRESH_MARK_TICKS);
         for head in self.heads.iter_mut() {
+            let id = head.get_id();
+            let direction = head_directions.get(&id).copied().or(direction);
             let prohibited_directions = DirectionFlags::empty();
             let move_head_event = HeadEvents::MOVE_HEAD { direction, prohibited_directions, map };
-            head.dispatch(move_head_event);
+            outcomes.extend(head_outcomes(observers, id, head.dispatch(move_head_event)));
+        }
+
+        self.apply_outcomes(outcomes);
+    }
+
+    fn set_direction_handler(&mut self, direction: Option<Direction>) {
+        match (self.focused_head, direction) {
+            (None, _) => self.next_direction = direction,
+            (Some(id), Some(direction)) => {
+                self.head_directions.insert(id, direction);
+            }
+            // The focused head follows the other heads again
+            (Some(id), None) => {
+                self.head_directions.remove(&id);
+            }
+        }
+    }
+
+    fn focus_head_handler(&mut self, id: Option<heads::Id>) {
+        match id {
+            Some(id) if !self.heads.contains(id) => (),
+            _ => self.focused_head = id,
+        }
+    }
+
+    fn focus_next_head_handler(&mut self) {
+        let next_id = self
+            .heads
+            .iter()
+            .map(|head| head.snapshot().id)
+            .filter(|&id| self.focused_head.is_none_or(|focused_id| id > focused_id))
+            .min();
+        self.focused_head = next_id;
+    }
+
+    fn kill_head_handler(&mut self, id: heads::Id, cause: KillCause) {
+        // Heads can be asked to die more than once, and their slot may hold a new head by the time
+        // the kill is processed. Only the first kill of the head itself goes through.
+        if self.remove_head(id).is_some() {
+            notify(&mut self.observers, BoardNotification::HEAD_KILLED { id, cause });
+        }
+    }
+
+    fn merge_head_handler(&mut self, id: heads::Id, into: heads::Id) {
+        // A head merged into one that is gone by now is lost with its volume
+        let Some(merged) = self.remove_head(id) else { return };
+        notify(&mut self.observers, BoardNotification::HEAD_KILLED { id, cause: KillCause::Merged });
+        if let Some(head) = self.heads.get_mut(into) {
+            head.absorb(&merged);
+            let volume = head.get_volume();
+            notify(&mut self.observers, BoardNotification::HEAD_MERGED { id, into, volume });
+        }
+    }
+
+    // The heads merged into the delivered one are delivered along with it
+    fn deliver_head_handler(&mut self, id: heads::Id) {
+        let Some(head) = self.remove_head(id) else { return };
+        let volume = head.get_volume();
+        let position = head.snapshot().position;
+        self.score.heads_delivered(volume);
+        notify(&mut self.observers, BoardNotification::HEAD_DELIVERED { id, position, volume });
+    }
+
+    fn slide_frame_handler(&mut self) {
+        self.map.slide();
+        self.slide_count += 1;
+        self.score.row_scrolled();
+        notify(&mut self.observers, BoardNotification::ROW_SCROLLED { slide_count: self.slide_count });
+        self.marks.retain_mut(|mark| match mark.position.y.checked_sub(1) {
+            Some(y) => {
+                mark.position.y = y;
+                true
+            }
+            None => false,
+        });
+
+        // Keep the heads on the same tiles. Heads on the dropped row die
+        let mut outcomes = Vec::new();
+        for head in self.heads.iter_mut() {
+            let id = head.get_id();
+            outcomes.extend(head_outcomes(&mut self.observers, id, head.dispatch(HeadEvents::<MapType>::SLIDE_FRAME)));
         }
+
+        self.apply_outcomes(outcomes);
     }
 
-    fn kill_head_handler(&mut self, id: heads::Id) {
-        self.heads.remove(id);
+    // The new head moves away from the separator at once, its outcomes are left to the caller
+    fn add_head_handler(&mut self, position: Coordinates, coming_from: Direction, parent_direction: Direction, lineage: Option<u64>) -> Vec<(heads::Id, HeadOutcome)> {
+        self.score.head_split();
+        let picker = DirectionPicker::new(self.rng.gen(), self.strategy.build());
+        let head = self.heads.add_head(position, coming_from, lineage, picker, &mut self.map);
+        let id = head.get_id();
+        notify(&mut self.observers, BoardNotification::HEAD_SPLIT { id, position });
+
+        let event = HeadEvents::MOVE_HEAD { direction: self.next_direction , prohibited_directions: DirectionFlags::from(parent_direction), map: &mut self.map};
+        head_outcomes(&mut self.observers, id, head.dispatch(event))
+    }
+
+    // A tick is resolved in rounds. The moves of a round are first checked for collisions, then the
+    // tiles they lead to are marked and the heads split from others make their first move, which
+    // is the next round. The merges of all the rounds are applied last, followed by the deliveries
+    // and the deaths.
+    fn apply_outcomes(&mut self, outcomes: Vec<(heads::Id, HeadOutcome)>) {
+        let mut merges = Vec::new();
+        let mut deliveries = Vec::new();
+        let mut deaths = Vec::new();
+        let mut round = outcomes;
+        while !round.is_empty() {
+            let mut splits = Vec::new();
+            let outcomes = self.resolve_collisions(round, &mut merges);
+            // Heads hitting a mark are settled before the moves of the round are marked, whatever
+            // the order the heads come in
+            for &(id, outcome) in &outcomes {
+                if let HeadOutcome::HAS_HIT_MARK { position } = outcome {
+                    match self.merge_target(id, position) {
+                        Some(into) => merges.push((id, into)),
+                        None => deaths.push((id, KillCause::Collision)),
+                    }
+                }
+            }
+            for (id, outcome) in outcomes {
+                match outcome {
+                    // Goals are left as they are
+                    HeadOutcome::HAS_MOVED { from, to, tile: TileType::Goal } => {
+                        notify(&mut self.observers, BoardNotification::HEAD_MOVED { id, from, to })
+                    }
+                    HeadOutcome::HAS_MOVED { from, to, .. } => match self.map.set_tile(to, TileType::Marked) {
+                        Ok(()) => {
+                            self.score.tile_marked();
+                            if let Some(head) = self.heads.get_mut(id) {
+                                head.tile_marked();
+                            }
+                            self.marks.push(TrailMark { position: to, id, tick: self.tick_count });
+                            notify_head_moved(&mut self.observers, id, from, to);
+                        }
+                        Err(error) => {
+                            notify(&mut self.observers, BoardNotification::BOARD_FAILED(BoardError::HeadFailed { id, error }));
+                            deaths.push((id, KillCause::Failure));
+                        }
+                    },
+                    HeadOutcome::HAS_SPLIT { position, coming_from, parent_direction } => {
+                        let lineage = self.heads.get(id).map(|head| head.get_lineage());
+                        splits.push((position, coming_from, parent_direction, lineage))
+                    }
+                    HeadOutcome::HAS_DIED(cause) => deaths.push((id, cause)),
+                    HeadOutcome::IS_DELIVERED => deliveries.push(id),
+                    HeadOutcome::HAS_HIT_MARK { .. } | HeadOutcome::IS_BLOCKED => (),
+                }
+            }
+
+            round = Vec::new();
+            for (position, coming_from, parent_direction, lineage) in splits {
+                round.extend(self.add_head_handler(position, coming_from, parent_direction, lineage));
+            }
+        }
+
+        // Heads merging into a head merged earlier go on as part of the head it went on as. A head
+        // meeting itself that way has taken in the others, it stays where it is.
+        let mut merged_into: Vec<(heads::Id, heads::Id)> = Vec::new();
+        for (id, mut into) in merges {
+            while let Some(&(_, next)) = merged_into.iter().find(|&&(merged, _)| merged == into) {
+                into = next;
+            }
+            if into != id {
+                self.merge_head_handler(id, into);
+                merged_into.push((id, into));
+            }
+        }
+        for id in deliveries {
+            self.deliver_head_handler(id);
+        }
+        for (id, cause) in deaths {
+            self.kill_head_handler(id, cause);
+        }
+    }
+
+    // Only one head at most goes on to a tile several heads move to, the others die or merge into it.
+    // A goal takes in every head moving to it.
+    fn resolve_collisions(&mut self, mut outcomes: Vec<(heads::Id, HeadOutcome)>, merges: &mut Vec<(heads::Id, heads::Id)>) -> Vec<(heads::Id, HeadOutcome)> {
+        let mut targets: Vec<(Coordinates, Vec<(heads::Id, u64)>)> = Vec::new();
+        for &(id, outcome) in &outcomes {
+            if let HeadOutcome::HAS_MOVED { to, tile, .. } = outcome {
+                if tile == TileType::Goal {
+                    continue;
+                }
+                let birth = self.heads.get(id).map_or(0, |head| head.get_birth());
+                match targets.iter_mut().find(|(position, _)| *position == to) {
+                    Some((_, contenders)) => contenders.push((id, birth)),
+                    None => targets.push((to, vec![(id, birth)])),
+                }
+            }
+        }
+
+        for (position, contenders) in targets.into_iter().filter(|(_, contenders)| contenders.len() > 1) {
+            let rule = self.collision_rule;
+            let winner = rule.winner(&contenders, &mut self.rng);
+            notify(&mut self.observers, BoardNotification::HEADS_COLLIDED { position, rule, winner });
+
+            let is_loser = |id: heads::Id| Some(id) != winner && contenders.iter().any(|&(contender, _)| contender == id);
+            outcomes.retain(|&(id, outcome)| !(is_loser(id) && matches!(outcome, HeadOutcome::HAS_MOVED { .. })));
+            for &(id, _) in contenders.iter().filter(|&&(id, _)| is_loser(id)) {
+                match (rule, winner) {
+                    (CollisionRule::Merge, Some(winner)) => merges.push((id, winner)),
+                    _ => outcomes.push((id, HeadOutcome::HAS_DIED(KillCause::Clash))),
+                }
+            }
+        }
+        outcomes
+    }
+
+    // Head that a head moving to the marked tile at `position` merges into, if any. The marks that
+    // count are the ones other heads made in the earlier rounds of the current move tick or during
+    // the FRESH_MARK_TICKS move ticks before it. The head merges into the head that made the latest
+    // of them on the tile, as long as that head is of the same lineage or the tile is the last one
+    // it marked before the round, which it stands on or has just left.
+    fn merge_target(&self, id: heads::Id, position: Coordinates) -> Option<heads::Id> {
+        if self.trail_rule != TrailRule::Merge {
+            return None;
+        }
+        let is_fresh = |mark: &&TrailMark| self.tick_count - mark.tick <= FRESH_MARK_TICKS;
+        let mark = self.marks.iter().rev().filter(is_fresh).find(|mark| mark.position == position && mark.id != id)?;
+        let marker = self.heads.get(mark.id)?;
+        let last_mark = self.marks.iter().rev().find(|other| other.id == mark.id)?;
+        let same_lineage = self.heads.get(id).is_some_and(|head| head.get_lineage() == marker.get_lineage());
+        (last_mark.position == position || same_lineage).then_some(mark.id)
+    }
+
+    fn save_game_handler(&mut self, path: &str) {
+        if let Err(error) = self.save().to_file(path) {
+            let error = BoardError::SaveFailed { path: path.to_string(), message: error.to_string() };
+            notify(&mut self.observers, BoardNotification::BOARD_FAILED(error));
+        }
+    }
+
+    fn load_game_handler(&mut self, path: &str) {
+        let result = SavedGame::from_file(path)
+            .map_err(|error| error.to_string())
+            .map(|saved| self.restore(saved));
+        if let Err(message) = result {
+            let error = BoardError::LoadFailed { path: path.to_string(), message };
+            notify(&mut self.observers, BoardNotification::BOARD_FAILED(error));
+        }
+    }
+
+    fn dispatch(&mut self, evt: BoardEvevents) {
+        let Ok(()) = self.process(evt);
+        self.publish_snapshot();
+    }
+
+    fn end_condition_met(&self) -> Option<GameOverReason> {
+        let conditions = &self.end_conditions;
+
+        // Head positions are brought back to level coordinates
+        let slide_count = self.slide_count as usize;
+        let reached_goal = |head: &SimpleHead| {
+            let position = head.snapshot().position;
+            let row = position.y + slide_count;
+            conditions.goal_row.is_some_and(|goal_row| row >= goal_row)
+                || conditions.goal_tile == Some(Coordinates { x: position.x, y: row })
+        };
+
+        if self.heads.iter().any(reached_goal) {
+            Some(GameOverReason::GoalReached)
+        } else if conditions.distance.is_some_and(|distance| self.slide_count >= distance) {
+            Some(GameOverReason::DistanceReached)
+        } else if conditions.survive_ticks.is_some_and(|ticks| self.tick_count >= ticks) {
+            Some(GameOverReason::TimeSurvived)
+        } else if conditions.deliver.is_some_and(|heads| self.score.delivered_heads >= heads) {
+            Some(GameOverReason::HeadsDelivered)
+        } else if self.heads.iter().next().is_none() {
+            Some(GameOverReason::AllHeadsDead)
+        } else {
+            None
+        }
     }
 }
 
-impl<MapType: Map> SimpleBoard<MapType> {
-    pub fn new(
-        events_sender: Sender<BoardEvevents>,
+impl<MapType: SavableMap> Hierarchy for SimpleBoard<MapType> {
+    type State = BoardState;
+    type Error = Infallible;
+
+    fn state(&self) -> BoardState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: BoardState) {
+        self.state = state;
+    }
+
+    fn parent(_state: BoardState) -> Option<BoardState> {
+        None
+    }
+
+    fn entry(&mut self, state: BoardState) -> Result<(), Infallible> {
+        if let BoardState::Over(reason) = state {
+            let game_over = GameOver { reason, score: self.score };
+            notify(&mut self.observers, BoardNotification::GAME_OVER(game_over));
+        }
+        Ok(())
+    }
+
+    // A game over is final
+    fn guard(&self, source: BoardState, _target: BoardState) -> bool {
+        source == BoardState::Running
+    }
+}
+
+impl<MapType: SavableMap> StateMachine<BoardEvevents> for SimpleBoard<MapType> {
+    type Output = ();
+
+    fn handle(&mut self, state: BoardState, evt: &mut BoardEvevents) -> Result<Response<BoardState, ()>, Infallible> {
+        if let BoardState::Over(_) = state {
+            return Ok(Response::Handled(()));
+        }
+
+        if let Some(recorder) = &mut self.recorder {
+            if let Err(error) = recorder.record(evt) {
+                let error = BoardError::RecordingStopped(error.to_string());
+                notify(&mut self.observers, BoardNotification::BOARD_FAILED(error));
+                self.recorder = None;
+            }
+        }
+
+        match evt {
+            BoardEvevents::SLIDE_FRAME_TICK => {
+                private::Sealed::slide_frame_handler(self)
+            }
+            BoardEvevents::MOVE_HEADS_TICK => {
+                private::Sealed::move_heads_handler(self, self.next_direction)
+            }
+            BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction } => {
+                private::Sealed::set_direction_handler(self, *direction)
+            }
+            BoardEvevents::FOCUS_HEAD { id } => {
+                private::Sealed::focus_head_handler(self, *id)
+            }
+            BoardEvevents::FOCUS_NEXT_HEAD => {
+                private::Sealed::focus_next_head_handler(self)
+            }
+            BoardEvevents::KILL_HEAD { id, cause } => {
+                private::Sealed::kill_head_handler(self, *id, *cause)
+            }
+            BoardEvevents::SAVE_GAME { path } => {
+                private::Sealed::save_game_handler(self, path)
+            }
+            BoardEvevents::LOAD_GAME { path } => {
+                private::Sealed::load_game_handler(self, path)
+            }
+            BoardEvevents::ADD_HEAD {
+                position,
+                coming_from,
+                parent_direction,
+            } => {
+                let outcomes = private::Sealed::add_head_handler(self, *position, *coming_from, *parent_direction, None);
+                private::Sealed::apply_outcomes(self, outcomes)
+            }
+        }
+
+        match private::Sealed::end_condition_met(self) {
+            Some(reason) => Ok(Response::Transition(BoardState::Over(reason), ())),
+            None => Ok(Response::Handled(())),
+        }
+    }
+}
+
+impl<MapType: SavableMap> SimpleBoard<MapType> {
+    // Returns the removed head, `None` if it is gone already. Observers are left to the caller
+    fn remove_head(&mut self, id: heads::Id) -> Option<SimpleHead> {
+        let head = self.heads.remove(id)?;
+        self.head_directions.remove(&id);
+        if self.focused_head == Some(id) {
+            self.focused_head = None;
+        }
+        Some(head)
+    }
+
+    pub fn game_over(&self) -> Option<GameOver> {
+        match self.state {
+            BoardState::Over(reason) => Some(GameOver { reason, score: self.score }),
+            BoardState::Running => None,
+        }
+    }
+
+    pub fn snapshot(&self) -> BoardSnapshot {
+        let tiles = (0..self.map.get_height())
+            .map(|y| {
+                (0..self.map.get_length())
+                    .map(|x| self.map.get_tile(Coordinates { x, y }).unwrap_or(TileType::Wall))
+                    .collect()
+            })
+            .collect();
+
+        BoardSnapshot {
+            tiles,
+            heads: self.heads.iter().map(|head| head.snapshot()).collect(),
+            focused_head: self.focused_head,
+            slide_count: self.slide_count,
+            tick_count: self.tick_count,
+            score: self.score,
+            game_over: self.game_over(),
+        }
+    }
+
+    pub fn score(&self) -> Score {
+        self.score
+    }
+
+    // Process the events already queued without waiting for new ones. Stops once the game is over
+    pub fn process_pending_events(&mut self) -> Option<GameOver> {
+        while self.game_over().is_none() {
+            match self.events_receiver.try_recv() {
+                Ok(evt) => private::Sealed::dispatch(self, evt),
+                Err(_) => break,
+            }
+        }
+        self.game_over()
+    }
+
+    // Send the current snapshot, then a new one after each processed event
+    pub fn publish_snapshots(&mut self, sender: SnapshotSender) {
+        self.snapshot_sender = Some(sender);
+        self.publish_snapshot();
+    }
+
+    fn publish_snapshot(&mut self) {
+        if let Some(sender) = &self.snapshot_sender {
+            // Nobody is listening anymore, stop building snapshots
+            if sender.send(self.snapshot()).is_err() {
+                self.snapshot_sender = None;
+            }
+        }
+    }
+
+    // Notify `observer` of everything happening on the board from now on
+    pub fn subscribe(&mut self, observer: Box<dyn BoardObserver>) {
+        self.observers.push(observer);
+    }
+
+    pub fn save(&self) -> SavedGame {
+        SavedGame {
+            header: LevelHeader {
+                strategy: Some(self.strategy),
+                collision: Some(self.collision_rule),
+                trail: Some(self.trail_rule),
+                end_conditions: self.end_conditions,
+                ..LevelHeader::default()
+            },
+            rows: self.snapshot().tiles.into(),
+            seed: self.seed,
+            slide_count: self.slide_count,
+            tick_count: self.tick_count,
+            next_direction: self.next_direction,
+            focused_head: self.focused_head,
+            head_directions: self.head_directions.clone(),
+            state: self.state,
+            score: self.score,
+            rng: RngState::of(&self.rng),
+            generator: self.map.generator_state(),
+            head_list: self.heads.save(),
+            heads: self.heads.iter().map(|head| head.save()).collect(),
+            marks: self.marks.clone(),
+        }
+    }
+
+    // Go on with a saved game in place of the current one. Events still queued apply to the saved game
+    pub fn restore(&mut self, saved: SavedGame) {
+        self.map.restore(saved.rows, saved.generator);
+        self.strategy = saved.header.strategy.unwrap_or_default();
+        self.collision_rule = saved.header.collision.unwrap_or_default();
+        self.trail_rule = saved.header.trail.unwrap_or_default();
+        self.marks = saved.marks;
+        self.end_conditions = saved.header.end_conditions;
+        self.seed = saved.seed;
+        self.slide_count = saved.slide_count;
+        self.tick_count = saved.tick_count;
+        self.next_direction = saved.next_direction;
+        self.focused_head = saved.focused_head;
+        self.head_directions = saved.head_directions;
+        self.state = saved.state;
+        self.score = saved.score;
+        self.rng = saved.rng.build();
+
+        // Heads saved while dying are removed right away. Delivered ones are already in the score,
+        // they only give their slot back
+        let mut deaths = Vec::new();
+        let mut deliveries = Vec::new();
+        self.heads.restore(saved.head_list);
+        for head in saved.heads {
+            match head.state {
+                HeadState::Dying(cause) => deaths.push((head.id, cause)),
+                HeadState::Delivered => deliveries.push(head.id),
+                _ => (),
+            }
+            self.heads.insert(SimpleHead::restore(head, self.strategy.build()));
+        }
+        for id in deliveries {
+            self.remove_head(id);
+        }
+        for (id, cause) in deaths {
+            private::Sealed::kill_head_handler(self, id, cause);
+        }
+    }
+
+    // Log every event received from now on
+    pub fn record_events(&mut self, recorder: Recorder) {
+        self.recorder = Some(recorder);
+    }
+}
+
+impl<MapType: SavableMap> Board for SimpleBoard<MapType> {
+    fn new(
         events_receiver: Receiver<BoardEvevents>,
+        config: BoardConfig,
     ) -> Self {
-        let mut map = MapType::new();
-
         // Create first head
-        let first_head_position = Coordinates {
-            x: map.get_length() / 2,
-            y: 0,
-        };
+        let first_head_position = config.map.spawn_point;
+        let mut map = MapType::new(config.map);
+        let mut rng = ChaCha8Rng::seed_from_u64(config.seed);
 
-        let mut heads = HeadList::new();
-        heads.add_head(first_head_position,Direction::Down,events_sender.clone(), &mut map);
+        let mut heads = HeadList::<SimpleHead>::new();
+        let picker = DirectionPicker::new(rng.gen(), config.strategy.build());
+        let first_head_id = heads.add_head(first_head_position,Direction::Down, None, picker, &mut map).get_id();
+
+        // The first head marks the tile it spawns on, as if it had just moved there
+        let mut marks = Vec::new();
+        if map.get_tile(first_head_position) == Ok(TileType::Free) && map.set_tile(first_head_position, TileType::Marked).is_ok() {
+            marks.push(TrailMark { position: first_head_position, id: first_head_id, tick: 0 });
+        }
         Self {
             map,
             heads,
-            events_sender,
             events_receiver,
             next_direction: None,
+            focused_head: None,
+            head_directions: BTreeMap::new(),
+            seed: config.seed,
+            rng,
+            strategy: config.strategy,
+            collision_rule: config.collision_rule,
+            trail_rule: config.trail_rule,
+            marks,
+            slide_count: 0,
+            tick_count: 0,
+            score: Score::default(),
+            end_conditions: config.end_conditions,
+            state: BoardState::Running,
+            snapshot_sender: None,
+            observers: Vec::new(),
+            recorder: None,
         }
     }
 
-    pub fn run(&mut self) {
-        while let Ok(evt) = self.events_receiver.recv() {
-            match evt {
-                BoardEvevents::SLIDE_FRAME_TICK => (),
-                BoardEvevents::MOVE_HEADS_TICK => {
-                    private::Sealed::move_heads_handler(self, self.next_direction)
-                }
-                BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction } => {
-                    self.next_direction = direction
-                }
-                BoardEvevents::KILL_HEAD { id } => {
-                    private::Sealed::kill_head_handler(self, id)
-                }
-                BoardEvevents::ADD_HEAD {
-                    position,
-                    coming_from,
-                    parent_direction,
-                } => {
-                    let head = self.heads.add_head(position, coming_from, self.events_sender.clone(), &mut self.map);
-                    let event = HeadEvents::MOVE_HEAD { direction: self.next_direction , prohibited_directions: DirectionFlags::from(parent_direction), map: &mut self.map};
-                    head.dispatch(event)
-                }
+    fn run(&mut self) -> Result<GameOver, FlowError> {
+        loop {
+            let evt = self.events_receiver.recv().map_err(|_| FlowError::ChannelClosed)?;
+            private::Sealed::dispatch(self, evt);
+            if let Some(game_over) = self.game_over() {
+                return Ok(game_over);
+            }
+        }
+    }
+}
+
+fn notify(observers: &mut [Box<dyn BoardObserver>], notification: BoardNotification) {
+    for observer in observers.iter_mut() {
+        observer.notify(&notification);
+    }
+}
+
+// A head failing for anything else than the rules of the game is a bug worth reporting. It is killed.
+fn head_outcomes(observers: &mut [Box<dyn BoardObserver>], id: heads::Id, result: Result<Vec<HeadOutcome>, FlowError>) -> Vec<(heads::Id, HeadOutcome)> {
+    let outcomes = result.unwrap_or_else(|error| {
+        notify(observers, BoardNotification::BOARD_FAILED(BoardError::HeadFailed { id, error }));
+        vec![HeadOutcome::HAS_DIED(KillCause::Failure)]
+    });
+    outcomes.into_iter().map(|outcome| (id, outcome)).collect()
+}
+
+// Moving heads mark the tile they land on
+fn notify_head_moved(observers: &mut [Box<dyn BoardObserver>], id: heads::Id, from: Coordinates, to: Coordinates) {
+    notify(observers, BoardNotification::HEAD_MOVED { id, from, to });
+    notify(observers, BoardNotification::TILE_MARKED { position: to });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+
+    use crate::map::SimpleMap;
+    use crate::save;
+
+    use super::*;
+
+    struct TestBoard {
+        board: SimpleBoard<SimpleMap>,
+        events: mpsc::Sender<BoardEvevents>,
+        notifications: Receiver<BoardNotification>,
+    }
+
+    impl TestBoard {
+        fn send(&mut self, evt: BoardEvevents) {
+            self.events.send(evt).unwrap();
+            self.board.process_pending_events();
+        }
+
+        // Steer a head, or the heads without a direction of their own
+        fn steer(&mut self, id: Option<heads::Id>, direction: Direction) {
+            self.send(BoardEvevents::FOCUS_HEAD { id });
+            self.send(BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction: Some(direction) });
+        }
+
+        // The added head moves at once in `direction`
+        fn add_head(&mut self, position: Coordinates, coming_from: Direction, direction: Direction) -> heads::Id {
+            let ids = |board: &Self| board.board.snapshot().heads.iter().map(|head| head.id).collect::<Vec<_>>();
+            let before = ids(self);
+            self.steer(None, direction);
+            self.send(BoardEvevents::ADD_HEAD { position, coming_from, parent_direction: coming_from });
+            ids(self).into_iter().find(|id| !before.contains(id)).unwrap()
+        }
+
+        fn head_at(&self, position: Coordinates) -> heads::Id {
+            let heads = self.board.snapshot().heads;
+            heads.iter().find(|head| head.position == position).unwrap().id
+        }
+
+        // Positions and volumes of the heads, whatever their slots
+        fn heads(&self) -> Vec<(Coordinates, u64)> {
+            let mut heads: Vec<_> = self.board.heads.iter().map(|head| (head.snapshot().position, head.get_volume())).collect();
+            heads.sort_by_key(|&(position, _)| (position.x, position.y));
+            heads
+        }
+
+        fn notifications(&self) -> Vec<BoardNotification> {
+            self.notifications.try_iter().collect()
+        }
+    }
+
+    // Rows are listed from the top of the map down
+    fn config(rows: &[&str], spawn_point: Coordinates) -> BoardConfig {
+        let rows = rows
+            .iter()
+            .rev()
+            .map(|row| row.chars().map(|c| TileType::from_char(c).unwrap()).collect())
+            .collect();
+        BoardConfig {
+            map: MapConfig::from_rows(rows, Some(spawn_point), 0),
+            seed: 0,
+            strategy: StrategyKind::default(),
+            collision_rule: CollisionRule::default(),
+            trail_rule: TrailRule::default(),
+            end_conditions: EndConditions::default(),
+        }
+    }
+
+    fn start(config: BoardConfig) -> TestBoard {
+        let (events, events_receiver) = mpsc::channel();
+        let (notification_sender, notifications) = mpsc::channel();
+        let mut board = SimpleBoard::<SimpleMap>::new(events_receiver, config);
+        board.subscribe(Box::new(notification_sender));
+        TestBoard { board, events, notifications }
+    }
+
+    const OPEN: [&str; 4] = ["....", "....", "....", "...."];
+
+    // A head moves down to the tile another head has just left, the two heads are in the given
+    // slot order
+    fn merge_into_trail_just_left(left_first: bool) -> TestBoard {
+        let mut board = start(BoardConfig { trail_rule: TrailRule::Merge, ..config(&OPEN, Coordinates { x: 3, y: 3 }) });
+        let spawned = board.head_at(Coordinates { x: 3, y: 3 });
+        let add_left = |board: &mut TestBoard| board.add_head(Coordinates { x: 1, y: 0 }, Direction::Down, Direction::Up);
+        let add_down = |board: &mut TestBoard| board.add_head(Coordinates { x: 0, y: 2 }, Direction::Left, Direction::Right);
+
+        // The head added once the spawned one is killed takes its slot, before the other one
+        let (left, down) = if left_first {
+            let down = add_down(&mut board);
+            board.send(BoardEvevents::KILL_HEAD { id: spawned, cause: KillCause::Failure });
+            (add_left(&mut board), down)
+        } else {
+            let left = add_left(&mut board);
+            board.send(BoardEvevents::KILL_HEAD { id: spawned, cause: KillCause::Failure });
+            (left, add_down(&mut board))
+        };
+        assert_eq!(left < down, left_first);
+
+        board.steer(Some(left), Direction::Right);
+        board.steer(Some(down), Direction::Down);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        board
+    }
+
+    #[test]
+    fn test_merge_is_independent_of_slot_order() {
+        let board = merge_into_trail_just_left(true);
+        let swapped = merge_into_trail_just_left(false);
+
+        assert_eq!(board.heads().len(), 1);
+        assert_eq!(board.heads()[0].0, Coordinates { x: 2, y: 1 });
+        assert_eq!(board.heads(), swapped.heads());
+    }
+
+    #[test]
+    fn test_merge_into_spawn_tile() {
+        let mut board = start(BoardConfig { trail_rule: TrailRule::Merge, ..config(&OPEN, Coordinates { x: 1, y: 1 }) });
+        let spawned = board.head_at(Coordinates { x: 1, y: 1 });
+        let added = board.add_head(Coordinates { x: 1, y: 3 }, Direction::Up, Direction::Down);
+
+        // The spawned head leaves the tile it spawned on as the other head moves to it
+        board.steer(Some(spawned), Direction::Left);
+        board.notifications();
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+
+        assert!(board.notifications().contains(&BoardNotification::HEAD_MERGED { id: added, into: spawned, volume: 2 }));
+        assert_eq!(board.heads(), vec![(Coordinates { x: 0, y: 1 }, 2)]);
+    }
+
+    // The spawned head and a younger one move to the tile between them
+    fn collide(collision_rule: CollisionRule) -> (TestBoard, heads::Id, heads::Id) {
+        let mut board = start(BoardConfig { collision_rule, ..config(&OPEN, Coordinates { x: 0, y: 1 }) });
+        let older = board.head_at(Coordinates { x: 0, y: 1 });
+        let younger = board.add_head(Coordinates { x: 2, y: 0 }, Direction::Down, Direction::Up);
+
+        board.steer(Some(older), Direction::Right);
+        board.steer(Some(younger), Direction::Left);
+        board.notifications();
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        (board, older, younger)
+    }
+
+    #[test]
+    fn test_collision_rules() {
+        let tile = Coordinates { x: 1, y: 1 };
+        let collided = |rule, winner| BoardNotification::HEADS_COLLIDED { position: tile, rule, winner };
+        let killed = |id, cause| BoardNotification::HEAD_KILLED { id, cause };
+
+        let (board, older, younger) = collide(CollisionRule::BothDie);
+        let notifications = board.notifications();
+        assert!(notifications.contains(&collided(CollisionRule::BothDie, None)));
+        assert!(notifications.contains(&killed(older, KillCause::Clash)) && notifications.contains(&killed(younger, KillCause::Clash)));
+        assert_eq!(board.board.game_over().map(|game_over| game_over.reason), Some(GameOverReason::AllHeadsDead));
+
+        let (board, older, younger) = collide(CollisionRule::Merge);
+        let notifications = board.notifications();
+        assert!(notifications.contains(&collided(CollisionRule::Merge, Some(older))));
+        assert!(notifications.contains(&killed(younger, KillCause::Merged)));
+        assert!(notifications.contains(&BoardNotification::HEAD_MERGED { id: younger, into: older, volume: 2 }));
+        assert_eq!(board.heads(), vec![(tile, 2)]);
+
+        let (board, older, younger) = collide(CollisionRule::OlderWins);
+        let notifications = board.notifications();
+        assert!(notifications.contains(&collided(CollisionRule::OlderWins, Some(older))));
+        assert!(notifications.contains(&killed(younger, KillCause::Clash)));
+        assert_eq!(board.heads(), vec![(tile, 1)]);
+
+        let (board, older, younger) = collide(CollisionRule::Random);
+        let winner = board.head_at(tile);
+        let loser = if winner == older { younger } else { older };
+        let notifications = board.notifications();
+        assert!(notifications.contains(&collided(CollisionRule::Random, Some(winner))));
+        assert!(notifications.contains(&killed(loser, KillCause::Clash)));
+        assert_eq!(board.heads(), vec![(tile, 1)]);
+    }
+
+    #[test]
+    fn test_restored_game_goes_on_the_same() {
+        let mut uninterrupted = start(config(&OPEN, Coordinates { x: 3, y: 3 }));
+        let killed = uninterrupted.add_head(Coordinates { x: 1, y: 0 }, Direction::Down, Direction::Up);
+        uninterrupted.send(BoardEvevents::KILL_HEAD { id: killed, cause: KillCause::Failure });
+
+        let saved = save::parse(&uninterrupted.board.save().to_string()).unwrap();
+        let mut restored = start(config(&OPEN, Coordinates { x: 0, y: 0 }));
+        restored.board.restore(saved);
+
+        // The next head takes the slot of the killed one, which its stale id does not reach
+        for board in [&mut uninterrupted, &mut restored] {
+            board.notifications();
+            board.add_head(Coordinates { x: 2, y: 0 }, Direction::Down, Direction::Up);
+            board.send(BoardEvevents::KILL_HEAD { id: killed, cause: KillCause::Failure });
+            for _ in 0..3 {
+                board.send(BoardEvevents::MOVE_HEADS_TICK);
             }
         }
+        assert_eq!(restored.notifications(), uninterrupted.notifications());
+        assert_eq!(restored.board.save(), uninterrupted.board.save());
+    }
+
+    #[test]
+    fn test_deliveries_end_the_game() {
+        let end_conditions = EndConditions { deliver: Some(2), ..EndConditions::default() };
+        let rows = ["o..o", "....", "...."];
+        let mut board = start(BoardConfig { end_conditions, ..config(&rows, Coordinates { x: 0, y: 1 }) });
+        let first = board.head_at(Coordinates { x: 0, y: 1 });
+        let second = board.add_head(Coordinates { x: 2, y: 0 }, Direction::Down, Direction::Up);
+        board.notifications();
+
+        board.steer(Some(first), Direction::Up);
+        board.steer(Some(second), Direction::Right);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        let goal = Coordinates { x: 0, y: 2 };
+        assert!(board.notifications().contains(&BoardNotification::HEAD_DELIVERED { id: first, position: goal, volume: 1 }));
+        assert_eq!(board.board.game_over(), None);
+
+        board.steer(Some(second), Direction::Up);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        let snapshot = board.board.snapshot();
+        assert_eq!(snapshot.game_over.map(|game_over| game_over.reason), Some(GameOverReason::HeadsDelivered));
+        assert_eq!(snapshot.score.delivered_heads, 2);
+        // Goals are never marked
+        assert_eq!(snapshot.tiles[2], vec![TileType::Goal, TileType::Free, TileType::Free, TileType::Goal]);
+    }
+
+    // A head splits on the separator, then goes on to the right while its child goes up. The
+    // parent made the marks of its first two ticks on the separator and to its right.
+    fn split_lineage() -> (TestBoard, heads::Id, heads::Id) {
+        let rows = ["......", "......", "......", "..+...", "......"];
+        let mut board = start(BoardConfig { trail_rule: TrailRule::Merge, ..config(&rows, Coordinates { x: 2, y: 0 }) });
+        let parent = board.head_at(Coordinates { x: 2, y: 0 });
+
+        board.steer(Some(parent), Direction::Up);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        board.steer(Some(parent), Direction::Right);
+        board.steer(None, Direction::Up);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        let child = board.head_at(Coordinates { x: 2, y: 2 });
+        (board, parent, child)
+    }
+
+    #[test]
+    fn test_merge_into_fresh_trail_of_lineage() {
+        let (mut board, parent, child) = split_lineage();
+
+        // The child moves down to the mark its parent made two ticks before
+        board.steer(None, Direction::Right);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+        board.notifications();
+        board.steer(None, Direction::Down);
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+
+        // The parent goes on with the volume of both heads
+        let volume = 2;
+        assert!(board.notifications().contains(&BoardNotification::HEAD_MERGED { id: child, into: parent, volume }));
+        assert_eq!(board.heads(), vec![(Coordinates { x: 5, y: 1 }, volume)]);
+    }
+
+    #[test]
+    fn test_hit_old_trail_of_lineage() {
+        let (mut board, _, child) = split_lineage();
+
+        // The child takes two more ticks to reach the same mark
+        for direction in [Direction::Up, Direction::Right, Direction::Down] {
+            board.steer(None, direction);
+            board.send(BoardEvevents::MOVE_HEADS_TICK);
+        }
+        board.notifications();
+        board.send(BoardEvevents::MOVE_HEADS_TICK);
+
+        let killed = BoardNotification::HEAD_KILLED { id: child, cause: KillCause::Collision };
+        assert!(board.notifications().contains(&killed));
+        assert_eq!(board.heads().len(), 1);
+    }
+
+    #[test]
+    fn test_merge_target_fresh_marks() {
+        let (mut board, parent, child) = split_lineage();
+        let mark = Coordinates { x: 3, y: 1 };
+
+        // The mark was made during the second move tick
+        board.board.tick_count = 2 + FRESH_MARK_TICKS;
+        assert_eq!(private::Sealed::merge_target(&board.board, child, mark), Some(parent));
+        board.board.tick_count += 1;
+        assert_eq!(private::Sealed::merge_target(&board.board, child, mark), None);
+
+        // Marks only count under the merge rule
+        board.board.tick_count = 2;
+        board.board.trail_rule = TrailRule::default();
+        assert_eq!(private::Sealed::merge_target(&board.board, child, mark), None);
     }
 }
@@ -0,0 +1,133 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::heads::Id;
+use crate::utils::Coordinates;
+
+// What happens when several heads move to the same tile during a tick
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum CollisionRule {
+    // Every head moving to the tile dies
+    BothDie,
+    // The heads become one, the oldest of them going on with the others merged into it
+    Merge,
+    // The oldest head takes the tile, the others die
+    #[default]
+    OlderWins,
+    // A head drawn from the seeded generator of the board takes the tile, the others die
+    Random,
+}
+
+impl CollisionRule {
+    // Head taking the tile among `contenders`, given with their birth rank. `None` if they all die.
+    pub fn winner(self, contenders: &[(Id, u64)], rng: &mut ChaCha8Rng) -> Option<Id> {
+        match self {
+            CollisionRule::BothDie => None,
+            CollisionRule::Merge | CollisionRule::OlderWins => {
+                contenders.iter().min_by_key(|&&(_, birth)| birth).map(|&(id, _)| id)
+            }
+            CollisionRule::Random if contenders.is_empty() => None,
+            CollisionRule::Random => Some(contenders[rng.gen_range(0..contenders.len())].0),
+        }
+    }
+}
+
+impl FromStr for CollisionRule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.trim() {
+            "both_die" => Ok(CollisionRule::BothDie),
+            "merge" => Ok(CollisionRule::Merge),
+            "older_wins" => Ok(CollisionRule::OlderWins),
+            "random" => Ok(CollisionRule::Random),
+            _ => Err(format!("Unknown collision rule `{}`, expected both_die, merge, older_wins or random", text)),
+        }
+    }
+}
+
+impl fmt::Display for CollisionRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CollisionRule::BothDie => write!(f, "both_die"),
+            CollisionRule::Merge => write!(f, "merge"),
+            CollisionRule::OlderWins => write!(f, "older_wins"),
+            CollisionRule::Random => write!(f, "random"),
+        }
+    }
+}
+
+// What happens to a head moving to a marked tile
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum TrailRule {
+    // The head dies
+    #[default]
+    Kill,
+    // The head merges into the head standing on the tile, or into the head of its own lineage that
+    // has just marked it. It dies if there is none.
+    Merge,
+}
+
+impl FromStr for TrailRule {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text.trim() {
+            "kill" => Ok(TrailRule::Kill),
+            "merge" => Ok(TrailRule::Merge),
+            _ => Err(format!("Unknown trail rule `{}`, expected kill or merge", text)),
+        }
+    }
+}
+
+impl fmt::Display for TrailRule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrailRule::Kill => write!(f, "kill"),
+            TrailRule::Merge => write!(f, "merge"),
+        }
+    }
+}
+
+// Tile marked by a head during the last move ticks
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TrailMark {
+    pub position: Coordinates,
+    pub id: Id,
+    // Move tick the tile was marked during
+    pub tick: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_collision_winner() {
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let young = Id { index: 0, generation: 1 };
+        let old = Id { index: 2, generation: 0 };
+        let contenders = [(young, 5), (old, 1)];
+
+        assert_eq!(CollisionRule::BothDie.winner(&contenders, &mut rng), None);
+        assert_eq!(CollisionRule::OlderWins.winner(&contenders, &mut rng), Some(old));
+        assert_eq!(CollisionRule::Merge.winner(&contenders, &mut rng), Some(old));
+
+        // The same seed draws the same winner
+        let winner = CollisionRule::Random.winner(&contenders, &mut rng);
+        assert!(winner == Some(young) || winner == Some(old));
+        assert_eq!(CollisionRule::Random.winner(&contenders, &mut ChaCha8Rng::seed_from_u64(3)), winner);
+
+        for rule in [CollisionRule::BothDie, CollisionRule::Merge, CollisionRule::OlderWins, CollisionRule::Random] {
+            assert_eq!(rule.to_string().parse(), Ok(rule));
+        }
+        assert!("first".parse::<CollisionRule>().is_err());
+        assert_eq!("merge".parse(), Ok(TrailRule::Merge));
+        assert_eq!(TrailRule::Kill.to_string(), "kill");
+    }
+}
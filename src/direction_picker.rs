@@ -1,27 +1,102 @@
 
 #[cfg(not(test))]
 pub type DirectionPicker = private::DirectionPicker;
+
+// Boards under test build seeded pickers, heads under test get mocks with their expectations
 #[cfg(test)]
-pub type DirectionPicker = private::MockDirectionPicker;
+pub enum DirectionPicker {
+    Seeded(Box<private::DirectionPicker>),
+    Mocked(private::MockDirectionPicker),
+}
+
 #[cfg(test)]
-pub type PickerCtx = private::__mock_MockDirectionPicker::__pick::Context;
+impl DirectionPicker {
+    pub fn new(seed: u64, strategy: Box<dyn crate::direction_strategy::DirectionStrategy>) -> Self {
+        DirectionPicker::Seeded(Box::new(private::DirectionPicker::new(seed, strategy)))
+    }
+
+    pub fn restore(rng: crate::save::RngState, strategy: Box<dyn crate::direction_strategy::DirectionStrategy>) -> Self {
+        DirectionPicker::Seeded(Box::new(private::DirectionPicker::restore(rng, strategy)))
+    }
+
+    pub fn rng_state(&self) -> crate::save::RngState {
+        match self {
+            DirectionPicker::Seeded(picker) => picker.rng_state(),
+            DirectionPicker::Mocked(picker) => picker.rng_state(),
+        }
+    }
+
+    pub fn pick(
+        &mut self,
+        coming_from: crate::utils::Direction,
+        prohibited_directions: &mut crate::utils::DirectionFlags,
+    ) -> Result<crate::utils::Direction, crate::error::FlowError> {
+        match self {
+            DirectionPicker::Seeded(picker) => picker.pick(coming_from, prohibited_directions),
+            DirectionPicker::Mocked(picker) => picker.pick(coming_from, prohibited_directions),
+        }
+    }
+}
+
+#[cfg(test)]
+impl Default for DirectionPicker {
+    fn default() -> Self {
+        DirectionPicker::Mocked(private::MockDirectionPicker::default())
+    }
+}
+
+// Expectations are set on the mock itself
+#[cfg(test)]
+impl std::ops::Deref for DirectionPicker {
+    type Target = private::MockDirectionPicker;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            DirectionPicker::Mocked(picker) => picker,
+            DirectionPicker::Seeded(_) => panic!("Seeded pickers have no expectations"),
+        }
+    }
+}
+
+#[cfg(test)]
+impl std::ops::DerefMut for DirectionPicker {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            DirectionPicker::Mocked(picker) => picker,
+            DirectionPicker::Seeded(_) => panic!("Seeded pickers have no expectations"),
+        }
+    }
+}
 
 mod private{
-    use rand::{thread_rng, Rng};
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+    use crate::direction_strategy::DirectionStrategy;
+    use crate::error::FlowError;
+    use crate::save::RngState;
     use crate::utils::{DirectionFlags, Direction};
     
 
-    pub struct DirectionPicker{}
+    pub struct DirectionPicker{
+        rng: ChaCha8Rng,
+        strategy: Box<dyn DirectionStrategy>,
+    }
 
     #[cfg_attr(test, mockall::automock)]
     impl DirectionPicker{
-    pub fn pick(prohibited_directions: &mut DirectionFlags) -> Direction {
-        // Full bitfield means that all dirs have already been explored, which should not be possible. If it is the case the map is ill-formed
-        assert!(
-            prohibited_directions.contains(!DirectionFlags::all()),
-            "No more available dirs to pick"
-        );
+    pub fn new(seed: u64, strategy: Box<dyn DirectionStrategy>) -> Self {
+        DirectionPicker { rng: ChaCha8Rng::seed_from_u64(seed), strategy }
+    }
+
+    pub fn restore(rng: RngState, strategy: Box<dyn DirectionStrategy>) -> Self {
+        DirectionPicker { rng: rng.build(), strategy }
+    }
 
+    pub fn rng_state(&self) -> RngState {
+        RngState::of(&self.rng)
+    }
+
+    pub fn pick(&mut self, coming_from: Direction, prohibited_directions: &mut DirectionFlags) -> Result<Direction, FlowError> {
         // Generate a vector containing all available directions
         let mut dir_vec = Vec::<Direction>::new();
         for dir in [
@@ -35,15 +110,81 @@ mod private{
             }
         }
 
-        // Select a random direction among available ones
-        let mut rng = thread_rng();
-        let random_index = rng.gen_range(0..dir_vec.len());
-        let picked_direction = dir_vec[random_index];
+        // Full bitfield means that all dirs have already been explored, the head has nowhere to go
+        if dir_vec.is_empty() {
+            return Err(FlowError::Trapped);
+        }
+
+        // Let the strategy select a direction among available ones
+        let picked_direction = self.strategy.choose(&dir_vec, coming_from, &mut self.rng);
 
         // Make the direction unavailable in prohibited_directions
         prohibited_directions.insert(picked_direction);
 
-        picked_direction
+        Ok(picked_direction)
+    }
+}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::private;
+    use crate::direction_strategy::UniformRandom;
+    use crate::error::FlowError;
+    use crate::utils::{Direction, DirectionFlags};
+
+    #[test]
+    fn test_pick_available_direction() {
+        let mut picker = private::DirectionPicker::new(0, Box::new(UniformRandom));
+        for _ in 0..20 {
+            let mut prohibited_directions = DirectionFlags::from(Direction::Up) | Direction::Left;
+            let picked = picker.pick(Direction::Down, &mut prohibited_directions).unwrap();
+
+            assert!(picked == Direction::Down || picked == Direction::Right);
+            assert!(prohibited_directions.contains(picked));
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_picks() {
+        let mut first = private::DirectionPicker::new(11, Box::new(UniformRandom));
+        let mut second = private::DirectionPicker::new(11, Box::new(UniformRandom));
+
+        for _ in 0..50 {
+            let mut first_prohibited = DirectionFlags::empty();
+            let mut second_prohibited = DirectionFlags::empty();
+            for _ in 0..3 {
+                assert_eq!(
+                    first.pick(Direction::Down, &mut first_prohibited),
+                    second.pick(Direction::Down, &mut second_prohibited)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_restored_picks() {
+        let mut picker = private::DirectionPicker::new(5, Box::new(UniformRandom));
+        picker.pick(Direction::Down, &mut DirectionFlags::empty()).unwrap();
+
+        // A picker restored from the state of another one goes on with the same picks
+        let mut restored = private::DirectionPicker::restore(picker.rng_state(), Box::new(UniformRandom));
+        for _ in 0..20 {
+            assert_eq!(
+                picker.pick(Direction::Down, &mut DirectionFlags::empty()),
+                restored.pick(Direction::Down, &mut DirectionFlags::empty())
+            );
+        }
+    }
+
+    #[test]
+    fn test_trapped() {
+        let mut picker = private::DirectionPicker::new(0, Box::new(UniformRandom));
+        let mut prohibited_directions = DirectionFlags::all();
+        prohibited_directions.remove(Direction::Left);
+
+        assert_eq!(picker.pick(Direction::Down, &mut prohibited_directions), Ok(Direction::Left));
+        assert_eq!(picker.pick(Direction::Down, &mut prohibited_directions), Err(FlowError::Trapped));
     }
 }
-}
\ No newline at end of file
@@ -0,0 +1,239 @@
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::utils::Direction;
+
+pub trait DirectionStrategy: Send {
+    // Choose one of the `available` directions (never empty) for a head that entered its tile from `coming_from`
+    fn choose(&self, available: &[Direction], coming_from: Direction, rng: &mut ChaCha8Rng) -> Direction;
+}
+
+pub struct UniformRandom;
+
+impl DirectionStrategy for UniformRandom {
+    fn choose(&self, available: &[Direction], _coming_from: Direction, rng: &mut ChaCha8Rng) -> Direction {
+        available[rng.gen_range(0..available.len())]
+    }
+}
+
+pub struct Weighted {
+    // Indexed as Up, Down, Right, Left
+    weights: [u32; 4],
+}
+
+impl Weighted {
+    pub fn new(up: u32, down: u32, right: u32, left: u32) -> Self {
+        Weighted {
+            weights: [up, down, right, left],
+        }
+    }
+
+    fn weight(&self, direction: Direction) -> u32 {
+        match direction {
+            Direction::Up => self.weights[0],
+            Direction::Down => self.weights[1],
+            Direction::Right => self.weights[2],
+            Direction::Left => self.weights[3],
+        }
+    }
+}
+
+impl DirectionStrategy for Weighted {
+    fn choose(&self, available: &[Direction], coming_from: Direction, rng: &mut ChaCha8Rng) -> Direction {
+        let total: u32 = available.iter().map(|&direction| self.weight(direction)).sum();
+
+        // Only zero weighted directions are left
+        if total == 0 {
+            return UniformRandom.choose(available, coming_from, rng);
+        }
+
+        let mut draw = rng.gen_range(0..total);
+        for &direction in available {
+            let weight = self.weight(direction);
+            if draw < weight {
+                return direction;
+            }
+            draw -= weight;
+        }
+        unreachable!("Draw exceeds the total weight")
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Side {
+    Left,
+    Right,
+}
+
+pub struct WallHugging {
+    side: Side,
+}
+
+impl WallHugging {
+    pub fn new(side: Side) -> Self {
+        WallHugging { side }
+    }
+}
+
+impl DirectionStrategy for WallHugging {
+    fn choose(&self, available: &[Direction], coming_from: Direction, _rng: &mut ChaCha8Rng) -> Direction {
+        // Turn towards the hugged side first, then go straight, then turn away from it
+        let heading = coming_from.reverse();
+        let preferences = match self.side {
+            Side::Left => [heading.turn_left(), heading, heading.turn_right(), coming_from],
+            Side::Right => [heading.turn_right(), heading, heading.turn_left(), coming_from],
+        };
+
+        preferences
+            .into_iter()
+            .find(|direction| available.contains(direction))
+            .unwrap_or(available[0])
+    }
+}
+
+pub struct GreedyTowardEdge {
+    edge: Direction,
+}
+
+impl GreedyTowardEdge {
+    pub fn new(edge: Direction) -> Self {
+        GreedyTowardEdge { edge }
+    }
+}
+
+impl DirectionStrategy for GreedyTowardEdge {
+    fn choose(&self, available: &[Direction], coming_from: Direction, rng: &mut ChaCha8Rng) -> Direction {
+        if available.contains(&self.edge) {
+            return self.edge;
+        }
+
+        // Go sideways rather than away from the edge
+        let sideways: Vec<Direction> = available
+            .iter()
+            .copied()
+            .filter(|&direction| direction != self.edge.reverse())
+            .collect();
+        if sideways.is_empty() {
+            available[0]
+        } else {
+            UniformRandom.choose(&sideways, coming_from, rng)
+        }
+    }
+}
+
+// Serializable description of a strategy, as found in levels and on the command line
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum StrategyKind {
+    #[default]
+    Uniform,
+    Weighted { up: u32, down: u32, right: u32, left: u32 },
+    WallHugging(Side),
+    Greedy(Direction),
+}
+
+impl StrategyKind {
+    pub fn build(self) -> Box<dyn DirectionStrategy> {
+        match self {
+            StrategyKind::Uniform => Box::new(UniformRandom),
+            StrategyKind::Weighted { up, down, right, left } => Box::new(Weighted::new(up, down, right, left)),
+            StrategyKind::WallHugging(side) => Box::new(WallHugging::new(side)),
+            StrategyKind::Greedy(edge) => Box::new(GreedyTowardEdge::new(edge)),
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = String;
+
+    // Accepted forms: `uniform`, `weighted <up> <down> <right> <left>`, `hug left|right`, `greedy <direction>`
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let invalid = || format!("Invalid direction strategy `{}`", text);
+
+        match words.as_slice() {
+            ["uniform"] => Ok(StrategyKind::Uniform),
+            ["weighted", weights @ ..] if weights.len() == 4 => {
+                let weights: Vec<u32> = weights
+                    .iter()
+                    .map(|weight| weight.parse().map_err(|_| invalid()))
+                    .collect::<Result<_, _>>()?;
+                Ok(StrategyKind::Weighted {
+                    up: weights[0],
+                    down: weights[1],
+                    right: weights[2],
+                    left: weights[3],
+                })
+            }
+            ["hug", "left"] => Ok(StrategyKind::WallHugging(Side::Left)),
+            ["hug", "right"] => Ok(StrategyKind::WallHugging(Side::Right)),
+            ["greedy", edge] => Direction::from_name(edge).map(StrategyKind::Greedy).ok_or_else(invalid),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+// Written in the form accepted by `from_str`
+impl fmt::Display for StrategyKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StrategyKind::Uniform => write!(f, "uniform"),
+            StrategyKind::Weighted { up, down, right, left } => write!(f, "weighted {} {} {} {}", up, down, right, left),
+            StrategyKind::WallHugging(Side::Left) => write!(f, "hug left"),
+            StrategyKind::WallHugging(Side::Right) => write!(f, "hug right"),
+            StrategyKind::Greedy(edge) => write!(f, "greedy {}", edge.name()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_strategies_preferences() {
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let all = [Direction::Up, Direction::Down, Direction::Right, Direction::Left];
+
+        // Heading up
+        let hug_left = WallHugging::new(Side::Left);
+        assert_eq!(hug_left.choose(&all, Direction::Down, &mut rng), Direction::Left);
+        assert_eq!(hug_left.choose(&[Direction::Right, Direction::Up], Direction::Down, &mut rng), Direction::Up);
+        let hug_right = WallHugging::new(Side::Right);
+        assert_eq!(hug_right.choose(&all, Direction::Down, &mut rng), Direction::Right);
+
+        let greedy = GreedyTowardEdge::new(Direction::Up);
+        assert_eq!(greedy.choose(&all, Direction::Left, &mut rng), Direction::Up);
+        for _ in 0..20 {
+            let direction = greedy.choose(&[Direction::Down, Direction::Right, Direction::Left], Direction::Up, &mut rng);
+            assert_ne!(direction, Direction::Down);
+        }
+
+        let weighted = Weighted::new(0, 5, 0, 1);
+        for _ in 0..20 {
+            let direction = weighted.choose(&[Direction::Up, Direction::Down, Direction::Right], Direction::Left, &mut rng);
+            assert_eq!(direction, Direction::Down);
+        }
+    }
+
+    #[test]
+    fn test_parse_strategy_kind() {
+        assert_eq!("uniform".parse(), Ok(StrategyKind::Uniform));
+        assert_eq!(
+            "weighted 1 4 1 1".parse(),
+            Ok(StrategyKind::Weighted { up: 1, down: 4, right: 1, left: 1 })
+        );
+        assert_eq!("hug right".parse(), Ok(StrategyKind::WallHugging(Side::Right)));
+        assert_eq!("greedy up".parse(), Ok(StrategyKind::Greedy(Direction::Up)));
+        assert!("greedy sideways".parse::<StrategyKind>().is_err());
+        assert!("weighted 1 2".parse::<StrategyKind>().is_err());
+
+        let weighted = StrategyKind::Weighted { up: 3, down: 0, right: 2, left: 2 };
+        assert_eq!(weighted.to_string().parse(), Ok(weighted));
+        assert_eq!(StrategyKind::Greedy(Direction::Left).to_string().parse(), Ok(StrategyKind::Greedy(Direction::Left)));
+    }
+}
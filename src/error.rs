@@ -0,0 +1,52 @@
+use std::fmt;
+
+use crate::heads::Id;
+use crate::utils::Coordinates;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FlowError {
+    // Every direction around the head is either prohibited or blocked
+    Trapped,
+    // The board is not listening to events anymore
+    ChannelClosed,
+    OutOfBounds(Coordinates),
+    // A head has been ordered to move onto a wall
+    WallCollision(Coordinates),
+}
+
+// Failures the board reports to its observers. The game goes on
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoardError {
+    // A head failed for anything else than the rules of the game. It is killed
+    HeadFailed { id: Id, error: FlowError },
+    SaveFailed { path: String, message: String },
+    LoadFailed { path: String, message: String },
+    // The replay file cannot be written anymore, the rest of the game is not recorded
+    RecordingStopped(String),
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BoardError::HeadFailed { id, error } => write!(f, "Head {} failed: {}", id, error),
+            BoardError::SaveFailed { path, message } => write!(f, "Cannot save the game to {}: {}", path, message),
+            BoardError::LoadFailed { path, message } => write!(f, "Cannot load the game from {}: {}", path, message),
+            BoardError::RecordingStopped(message) => write!(f, "Stopped recording the game: {}", message),
+        }
+    }
+}
+
+impl fmt::Display for FlowError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlowError::Trapped => write!(f, "Head is trapped"),
+            FlowError::ChannelClosed => write!(f, "Board events channel is closed"),
+            FlowError::OutOfBounds(position) => {
+                write!(f, "Position ({}, {}) is out of the map", position.x, position.y)
+            }
+            FlowError::WallCollision(position) => {
+                write!(f, "Cannot move onto the wall at ({}, {})", position.x, position.y)
+            }
+        }
+    }
+}
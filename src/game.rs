@@ -0,0 +1,197 @@
+use std::fs::{self, File};
+use std::io::BufWriter;
+
+use rand::Rng;
+
+use crate::app::App;
+use crate::board::BoardConfig;
+use crate::collision::CollisionRule;
+use crate::direction_strategy::StrategyKind;
+use crate::headless;
+use crate::level::{Level, LevelHeader};
+use crate::map::{parse_size, MapConfig};
+use crate::piston_renderer::PistonRenderer;
+use crate::replay::Recorder;
+use crate::renderer::Renderer;
+use crate::script::ScriptHeader;
+#[cfg(feature = "tui")]
+use crate::tui_renderer::TuiRenderer;
+
+pub const USAGE: &str =
+    "Usage: ruthless_flow [--level <file> | --size <width>x<height>] [--move-period <ms>] [--slide-period <ms>] [--seed <n>] [--strategy <strategy>] [--collision <rule>] [--record <file>] [--tui | --headless <script> | --replay <file>]";
+
+const DEFAULT_MOVE_HEADS_PERIOD_MS: i64 = 300;
+const DEFAULT_SLIDE_FRAME_PERIOD_MS: i64 = 2000;
+const DEFAULT_MAP_SIZE: (usize, usize) = (9, 16);
+pub const DEFAULT_TITLE: &str = "Ruthless flow";
+
+#[derive(Default, Clone, PartialEq, Eq)]
+pub enum Frontend {
+    #[default]
+    Window,
+    Terminal,
+    // No display nor timer, the events are read from a script file
+    Headless(String),
+    // Play a recorded game again, without display nor timer
+    Replay(String),
+}
+
+#[derive(Default, Clone)]
+pub struct GameConfig {
+    pub frontend: Frontend,
+    pub level: Option<String>,
+    // Size of the generated map used when no level is given
+    pub map_size: Option<(usize, usize)>,
+    // Tick periods given on the command line take precedence over the ones of the level
+    pub move_heads_period_ms: Option<i64>,
+    pub slide_frame_period_ms: Option<i64>,
+    // Same seed and same inputs give the same game. Picked randomly if not given
+    pub seed: Option<u64>,
+    // Overrides the direction strategy of the level
+    pub strategy: Option<StrategyKind>,
+    // Overrides the collision rule of the level
+    pub collision: Option<CollisionRule>,
+    // Replay file the events of the game are written to
+    pub record: Option<String>,
+}
+
+impl GameConfig {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut config = GameConfig::default();
+
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value after `{}`", arg));
+            match arg.as_str() {
+                "--tui" => config.frontend = Frontend::Terminal,
+                "--headless" => config.frontend = Frontend::Headless(value()?),
+                "--replay" => config.frontend = Frontend::Replay(value()?),
+                "--record" => config.record = Some(value()?),
+                "--level" => config.level = Some(value()?),
+                "--size" => config.map_size = Some(parse_size(&value()?)?),
+                "--move-period" => config.move_heads_period_ms = Some(parse_period(&value()?)?),
+                "--slide-period" => config.slide_frame_period_ms = Some(parse_period(&value()?)?),
+                "--strategy" => config.strategy = Some(value()?.parse()?),
+                "--collision" => config.collision = Some(value()?.parse()?),
+                "--seed" => {
+                    let seed = value()?;
+                    config.seed = Some(seed.parse().map_err(|_| format!("Invalid seed `{}`", seed))?)
+                }
+                _ => return Err(format!("Unexpected argument `{}`", arg)),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+fn parse_period(arg: &str) -> Result<i64, String> {
+    match arg.parse::<i64>() {
+        Ok(period) if period > 0 => Ok(period),
+        _ => Err(format!("Invalid tick period `{}`, expected a positive number of milliseconds", arg)),
+    }
+}
+
+// Load the level, or generate a map, and pick the seed of the game
+pub fn load_board_config(config: &GameConfig) -> Result<(LevelHeader, BoardConfig), String> {
+    if config.level.is_some() && config.map_size.is_some() {
+        return Err(String::from("A map size cannot be given along with a level"));
+    }
+
+    let seed = config.seed.unwrap_or_else(|| rand::thread_rng().gen());
+
+    let (header, map_config) = match &config.level {
+        Some(path) => {
+            let level = Level::from_file(path).map_err(|error| format!("{}: {}", path, error))?;
+            (level.header.clone(), level.into_map_config(seed))
+        }
+        None => {
+            let (width, height) = config.map_size.unwrap_or(DEFAULT_MAP_SIZE);
+            (LevelHeader::default(), MapConfig::generated(width, height, seed))
+        }
+    };
+    let board_config = BoardConfig {
+        map: map_config,
+        seed,
+        strategy: config.strategy.or(header.strategy).unwrap_or_default(),
+        collision_rule: config.collision.or(header.collision).unwrap_or_default(),
+        trail_rule: header.trail.unwrap_or_default(),
+        end_conditions: header.end_conditions,
+    };
+
+    Ok((header, board_config))
+}
+
+// Recorder for the game if one was asked for. Its header is enough to rebuild the board.
+pub fn open_recorder(config: &GameConfig, board_config: &BoardConfig) -> Result<Option<Recorder>, String> {
+    let path = match &config.record {
+        Some(path) => path,
+        None => return Ok(None),
+    };
+
+    // The level path is made absolute so that the replay can be moved around
+    let level = match &config.level {
+        Some(level) => Some(
+            fs::canonicalize(level)
+                .map_err(|error| format!("{}: {}", level, error))?
+                .to_string_lossy()
+                .into_owned(),
+        ),
+        None => None,
+    };
+    let header = ScriptHeader {
+        seed: Some(board_config.seed),
+        map_size: level.is_none().then_some((board_config.map.width, board_config.map.height)),
+        level,
+        strategy: Some(board_config.strategy),
+        collision: Some(board_config.collision_rule),
+    };
+
+    let file = File::create(path).map_err(|error| format!("{}: {}", path, error))?;
+    let recorder = Recorder::new(Box::new(BufWriter::new(file)), &header).map_err(|error| format!("{}: {}", path, error))?;
+    Ok(Some(recorder))
+}
+
+pub fn run(config: GameConfig) -> Result<(), String> {
+    match &config.frontend {
+        Frontend::Headless(script) => return headless::run(script, &config),
+        Frontend::Replay(replay) => return headless::replay(replay, &config),
+        _ => (),
+    }
+
+    // The window keeps the size of the map it was opened for, the boards are scaled to fit it
+    let (width, height) = config.map_size.unwrap_or(DEFAULT_MAP_SIZE);
+    let renderer = open_frontend(&config.frontend, DEFAULT_TITLE, width, height)?;
+
+    // The renderer is dropped along with the application, giving the terminal back before printing the summary
+    let last_game_over = App::new(config, renderer).run()?;
+    if let Some((game_over, seed)) = last_game_over {
+        print!("{}", game_over);
+        println!("Seed: {}", seed);
+    }
+    Ok(())
+}
+
+pub fn move_heads_period_ms(config: &GameConfig, header: &LevelHeader) -> i64 {
+    config
+        .move_heads_period_ms
+        .or(header.move_heads_period_ms)
+        .unwrap_or(DEFAULT_MOVE_HEADS_PERIOD_MS)
+}
+
+pub fn slide_frame_period_ms(config: &GameConfig, header: &LevelHeader) -> i64 {
+    config
+        .slide_frame_period_ms
+        .or(header.slide_frame_period_ms)
+        .unwrap_or(DEFAULT_SLIDE_FRAME_PERIOD_MS)
+}
+
+fn open_frontend(frontend: &Frontend, title: &str, map_length: usize, map_height: usize) -> Result<Box<dyn Renderer>, String> {
+    match frontend {
+        Frontend::Window => Ok(Box::new(PistonRenderer::new(title, map_length, map_height)?)),
+        #[cfg(feature = "tui")]
+        Frontend::Terminal => Ok(Box::new(TuiRenderer::new()?)),
+        #[cfg(not(feature = "tui"))]
+        Frontend::Terminal => Err(String::from("The terminal frontend is not available, rebuild with `--features tui`")),
+        Frontend::Headless(_) | Frontend::Replay(_) => unreachable!("Headless games have no frontend"),
+    }
+}
@@ -0,0 +1,86 @@
+use std::fmt;
+
+use crate::score::Score;
+use crate::utils::Coordinates;
+
+// Conditions ending the game besides losing every head. Rows and tiles are given in level
+// coordinates: row 0 is the bottom row of the map when the game starts, whatever scrolled since.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct EndConditions {
+    pub goal_row: Option<usize>,
+    pub goal_tile: Option<Coordinates>,
+    // Number of rows the map has to scroll
+    pub distance: Option<u64>,
+    // Number of move ticks to survive
+    pub survive_ticks: Option<u64>,
+    // Number of heads to deliver to the goal tiles
+    pub deliver: Option<u64>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum GameOverReason {
+    AllHeadsDead,
+    GoalReached,
+    DistanceReached,
+    TimeSurvived,
+    HeadsDelivered,
+}
+
+impl GameOverReason {
+    pub fn is_win(self) -> bool {
+        self != GameOverReason::AllHeadsDead
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            GameOverReason::AllHeadsDead => "all_heads_dead",
+            GameOverReason::GoalReached => "goal_reached",
+            GameOverReason::DistanceReached => "distance_reached",
+            GameOverReason::TimeSurvived => "time_survived",
+            GameOverReason::HeadsDelivered => "heads_delivered",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "all_heads_dead" => Some(GameOverReason::AllHeadsDead),
+            "goal_reached" => Some(GameOverReason::GoalReached),
+            "distance_reached" => Some(GameOverReason::DistanceReached),
+            "time_survived" => Some(GameOverReason::TimeSurvived),
+            "heads_delivered" => Some(GameOverReason::HeadsDelivered),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for GameOverReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameOverReason::AllHeadsDead => write!(f, "all heads are dead"),
+            GameOverReason::GoalReached => write!(f, "a head reached the goal"),
+            GameOverReason::DistanceReached => write!(f, "the flow went far enough"),
+            GameOverReason::TimeSurvived => write!(f, "the flow survived long enough"),
+            GameOverReason::HeadsDelivered => write!(f, "enough heads were delivered"),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct GameOver {
+    pub reason: GameOverReason,
+    pub score: Score,
+}
+
+impl GameOver {
+    pub fn headline(&self) -> String {
+        let outcome = if self.reason.is_win() { "You win" } else { "Game over" };
+        format!("{}: {}", outcome, self.reason)
+    }
+}
+
+impl fmt::Display for GameOver {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.headline())?;
+        write!(f, "{}", self.score)
+    }
+}
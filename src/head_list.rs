@@ -1,69 +1,146 @@
-use std::{ops::{Deref, DerefMut}, iter::FilterMap};
+use std::iter::FilterMap;
 
-use crate::{heads::{Head, Id}, utils::{Coordinates, Direction}, mpsc::Sender, board::BoardEvevents, map::Map};
+use crate::{heads::{Head, Id}, utils::{Coordinates, Direction}, map::Map, direction_picker::DirectionPicker, save::HeadListSave};
+
+type HeadsIterMut<'a, HeadType> = FilterMap<std::slice::IterMut<'a, Slot<HeadType>>, for<'r> fn(&'r mut Slot<HeadType>) -> Option<&'r mut HeadType>>;
+type HeadsIter<'a, HeadType> = FilterMap<std::slice::Iter<'a, Slot<HeadType>>, for<'r> fn(&'r Slot<HeadType>) -> Option<&'r HeadType>>;
+
+// The generation of a slot goes up each time its head is removed, so that the ids of the heads
+// it held before cannot reach the heads it holds afterwards
+pub struct Slot<HeadType>{
+    generation: u32,
+    head: Option<HeadType>,
+}
 
 pub struct HeadList<HeadType: Head>{
-    heads_vec: Vec<Option<HeadType>>
+    slots: Vec<Slot<HeadType>>,
+    // Indexes of the empty slots, the last one being reused first
+    free_slots: Vec<u32>,
+    // Birth rank of the next head
+    births: u64,
 }
 
 impl <HeadType : Head> HeadList<HeadType>{
     pub fn new() -> HeadList<HeadType>{
-        let heads_vec = Vec::new();
-        HeadList{heads_vec}
+        HeadList{slots: Vec::new(), free_slots: Vec::new(), births: 0}
     }
-    
-    pub fn iter_mut(&mut self) -> FilterMap<std::slice::IterMut<'_, Option<HeadType>>, for<'r> fn(&'r mut Option<HeadType>) -> Option<&'r mut HeadType>> {
-        let filtering_fn : fn(&mut Option<HeadType>) -> Option<&mut HeadType> = |x : &mut Option<HeadType>| if let Some(head) = x {Some(head)} else {None};
-        self.heads_vec.iter_mut().filter_map(filtering_fn)
+
+    pub fn iter_mut(&mut self) -> HeadsIterMut<'_, HeadType> {
+        let filtering_fn : fn(&mut Slot<HeadType>) -> Option<&mut HeadType> = |slot : &mut Slot<HeadType>| slot.head.as_mut();
+        self.slots.iter_mut().filter_map(filtering_fn)
     }
 
-    pub fn iter(&mut self) -> FilterMap<std::slice::Iter<'_, Option<HeadType>>, for<'r> fn(&'r Option<HeadType>) -> Option<&'r HeadType>> {
-        let filtering_fn : fn(& Option<HeadType>) -> Option<& HeadType> = |x : & Option<HeadType>| if let Some(head) = x {Some(head)} else {None};
-        self.heads_vec.iter().filter_map(filtering_fn)
+    pub fn iter(&self) -> HeadsIter<'_, HeadType> {
+        let filtering_fn : fn(& Slot<HeadType>) -> Option<& HeadType> = |slot : & Slot<HeadType>| slot.head.as_ref();
+        self.slots.iter().filter_map(filtering_fn)
     }
 
-    pub fn add_head(&mut self,    
+    pub fn add_head(&mut self,
     position: Coordinates,
     coming_from: Direction,
-    events_sender: Sender<BoardEvevents>,
+    // Lineage of the head, a new one starting with the head if not given
+    lineage: Option<u64>,
+    picker: DirectionPicker,
     map: &mut impl Map )-> &mut HeadType{
 
-        let mut free_slot_pos : Option<usize> = None;
-
-        // Try to put the head on an empty slot
-        for (pos, head) in self.heads_vec.iter_mut().enumerate(){
-            if let None = head {
-                let new_head = HeadType::new(pos as Id, position, coming_from, events_sender.clone(),  map);
-                head.replace(new_head);
-                free_slot_pos = Some(pos);
-                break;
+        // Put the head on an empty slot, or on a new one if none is available
+        let index = match self.free_slots.pop(){
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot{generation: 0, head: None});
+                (self.slots.len() - 1) as u32
             }
-        }
-        let new_slot_pos;
+        };
+
+        let slot = &mut self.slots[index as usize];
+        let id = Id{index, generation: slot.generation};
+        let birth = self.births;
+        self.births += 1;
+        slot.head.insert(HeadType::new(id, birth, lineage.unwrap_or(birth), position, coming_from, picker, map))
+    }
 
-        if let Some(free_slot_pos) = free_slot_pos{
-            new_slot_pos = free_slot_pos;
+    // Put back a head that already has an id, replacing the head holding that slot if any
+    pub fn insert(&mut self, mut head: HeadType){
+        let id = head.get_id();
+        while self.slots.len() <= id.index as usize{
+            self.free_slots.push(self.slots.len() as u32);
+            self.slots.push(Slot{generation: 0, head: None});
         }
-        
-        else{
-            // If no slot is available, push the head on a new slot
-            let new_head = HeadType::new(self.heads_vec.len() as Id, position, coming_from, events_sender.clone(),  map);
-            self.heads_vec.push(Some(new_head));
-            new_slot_pos = self.heads_vec.len() -1;
+        self.free_slots.retain(|&index| index != id.index);
+        self.births = self.births.max(head.get_birth() + 1);
+        self.slots[id.index as usize] = Slot{generation: id.generation, head: Some(head)};
+    }
+
+    pub fn save(&self) -> HeadListSave{
+        HeadListSave{
+            generations: self.slots.iter().map(|slot| slot.generation).collect(),
+            free_slots: self.free_slots.clone(),
+            births: self.births,
         }
-        
-        self.heads_vec[new_slot_pos].as_mut().unwrap()
+    }
 
+    // Empty the list down to the slots of a saved one. Its heads are to be inserted afterwards
+    pub fn restore(&mut self, saved: HeadListSave){
+        self.slots = saved.generations.into_iter().map(|generation| Slot{generation, head: None}).collect();
+        self.free_slots = saved.free_slots;
+        self.births = saved.births;
     }
 
-    pub fn remove(&mut self, id_of_head_to_remove: Id){
-        for head_opt in self.heads_vec.iter_mut(){
-            if let Some(head) = head_opt{
-                if head.get_id() == id_of_head_to_remove{
-                    head_opt.take();
-                }
-            }
+    // Ids of removed heads are stale, they reach no head anymore
+    pub fn get(&self, id: Id) -> Option<&HeadType>{
+        self.slots.get(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.head.as_ref())
+    }
 
-        }
+    pub fn get_mut(&mut self, id: Id) -> Option<&mut HeadType>{
+        self.slots.get_mut(id.index as usize)
+            .filter(|slot| slot.generation == id.generation)
+            .and_then(|slot| slot.head.as_mut())
     }
-}
\ No newline at end of file
+
+    pub fn contains(&self, id: Id) -> bool{
+        self.get(id).is_some()
+    }
+
+    // Returns the removed head, `None` if the id is stale
+    pub fn remove(&mut self, id: Id) -> Option<HeadType>{
+        let slot = self.slots.get_mut(id.index as usize).filter(|slot| slot.generation == id.generation)?;
+        let head = slot.head.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free_slots.push(id.index);
+        Some(head)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::heads::SimpleHead;
+    use crate::map::MockMap;
+
+    use super::*;
+
+    fn add_head(heads: &mut HeadList<SimpleHead>, map: &mut MockMap) -> Id {
+        let head = heads.add_head(Coordinates{x: 0, y: 0}, Direction::Down, None, DirectionPicker::default(), map);
+        head.get_id()
+    }
+
+    #[test]
+    fn test_stale_ids() {
+        let mut map = MockMap::default();
+        let mut heads = HeadList::<SimpleHead>::new();
+
+        let first = add_head(&mut heads, &mut map);
+        let second = add_head(&mut heads, &mut map);
+        assert!(heads.remove(first).is_some());
+
+        // The freed slot is reused by the next head, under another generation
+        let third = add_head(&mut heads, &mut map);
+        assert_eq!(third, Id{index: first.index, generation: first.generation + 1});
+        assert_eq!(heads.get(third).map(|head| head.get_birth()), Some(2));
+        assert!(heads.remove(first).is_none());
+        assert!(heads.get(first).is_none());
+        assert!(heads.contains(third) && heads.contains(second));
+        assert_eq!(heads.iter().count(), 2);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::mpsc;
+
+use crate::board::{Board, BoardConfig, BoardEvevents, SimpleBoard};
+use crate::game::{self, GameConfig};
+use crate::game_over::GameOver;
+use crate::map::{SimpleMap, TileType};
+use crate::notification::BoardNotification;
+use crate::replay::{Recorder, Replay};
+use crate::score::Score;
+use crate::script::{Script, ScriptHeader};
+use crate::snapshot::BoardSnapshot;
+
+#[derive(Debug, Default, PartialEq)]
+struct Statistics {
+    move_heads_ticks: u64,
+    slide_frame_ticks: u64,
+    direction_changes: u64,
+    heads_split: u64,
+    collisions: u64,
+    merges: u64,
+    // Number of heads killed for each cause
+    heads_killed: BTreeMap<&'static str, u64>,
+    errors: Vec<String>,
+}
+
+impl Statistics {
+    fn count(&mut self, event: &BoardEvevents) {
+        match event {
+            BoardEvevents::MOVE_HEADS_TICK => self.move_heads_ticks += 1,
+            BoardEvevents::SLIDE_FRAME_TICK => self.slide_frame_ticks += 1,
+            BoardEvevents::SET_NEXT_HEAD_DIRECTION { .. } => self.direction_changes += 1,
+            _ => (),
+        }
+    }
+
+    fn observe(&mut self, notification: &BoardNotification) {
+        match notification {
+            BoardNotification::HEAD_SPLIT { .. } => self.heads_split += 1,
+            BoardNotification::HEADS_COLLIDED { .. } => self.collisions += 1,
+            BoardNotification::HEAD_MERGED { .. } => self.merges += 1,
+            BoardNotification::HEAD_KILLED { cause, .. } => *self.heads_killed.entry(cause.name()).or_default() += 1,
+            BoardNotification::BOARD_FAILED(error) => self.errors.push(error.to_string()),
+            _ => (),
+        }
+    }
+}
+
+// Play a whole game from a script, then print the final map and statistics.
+// Each scripted event is fully processed before the next one is fed to the board.
+pub fn run(script_path: &str, config: &GameConfig) -> Result<(), String> {
+    let script = Script::from_file(script_path).map_err(|error| format!("{}: {}", script_path, error))?;
+
+    // Options given on the command line take precedence over the script header
+    let level = config.level.clone().or_else(|| level_path(script_path, &script.header));
+    let config = GameConfig {
+        map_size: config.map_size.or(if level.is_none() { script.header.map_size } else { None }),
+        level,
+        seed: config.seed.or(script.header.seed),
+        strategy: config.strategy.or(script.header.strategy),
+        collision: config.collision.or(script.header.collision),
+        record: config.record.clone(),
+        ..GameConfig::default()
+    };
+    let (_, board_config) = game::load_board_config(&config)?;
+    println!("Seed: {}", board_config.seed);
+    let recorder = game::open_recorder(&config, &board_config)?;
+
+    play(board_config, recorder, script.events).map(|outcome| print_outcome(&outcome))
+}
+
+// Play a recorded game again. Every event the board received is fed back in the same order.
+pub fn replay(replay_path: &str, config: &GameConfig) -> Result<(), String> {
+    let replay = Replay::from_file(replay_path).map_err(|error| format!("{}: {}", replay_path, error))?;
+
+    let config = GameConfig {
+        level: level_path(replay_path, &replay.header),
+        map_size: replay.header.map_size,
+        seed: replay.header.seed,
+        strategy: replay.header.strategy,
+        collision: replay.header.collision,
+        record: config.record.clone(),
+        ..GameConfig::default()
+    };
+    let (_, board_config) = game::load_board_config(&config)?;
+    println!("Seed: {}", board_config.seed);
+    let recorder = game::open_recorder(&config, &board_config)?;
+
+    let events = replay.events.into_iter().map(|(_, event)| event).collect();
+    play(board_config, recorder, events).map(|outcome| print_outcome(&outcome))
+}
+
+// Levels are looked for next to the file that references them
+fn level_path(file_path: &str, header: &ScriptHeader) -> Option<String> {
+    let level = header.level.as_ref()?;
+    let directory = Path::new(file_path).parent().unwrap_or(Path::new(""));
+    Some(directory.join(level).to_string_lossy().into_owned())
+}
+
+// State of the board once the events are played, or once the game is over
+#[derive(Debug)]
+struct Outcome {
+    snapshot: BoardSnapshot,
+    statistics: Statistics,
+    score: Score,
+    game_over: Option<GameOver>,
+}
+
+fn play(board_config: BoardConfig, recorder: Option<Recorder>, events: Vec<BoardEvevents>) -> Result<Outcome, String> {
+    let (events_sender, events_receiver) = mpsc::channel();
+    let mut board = SimpleBoard::<SimpleMap>::new(events_receiver, board_config);
+    if let Some(recorder) = recorder {
+        board.record_events(recorder);
+    }
+    let (notifications_sender, notifications) = mpsc::channel();
+    board.subscribe(Box::new(notifications_sender));
+    let mut statistics = Statistics::default();
+
+    let mut game_over = board.process_pending_events();
+    for event in events {
+        if game_over.is_some() {
+            break;
+        }
+        statistics.count(&event);
+        events_sender
+            .send(event)
+            .map_err(|_| String::from("The board stopped receiving events"))?;
+        game_over = board.process_pending_events();
+    }
+
+    for notification in notifications.try_iter() {
+        statistics.observe(&notification);
+    }
+
+    Ok(Outcome {
+        snapshot: board.snapshot(),
+        statistics,
+        score: board.score(),
+        game_over,
+    })
+}
+
+fn print_outcome(outcome: &Outcome) {
+    let Outcome { snapshot, statistics, score, game_over } = outcome;
+    for error in &statistics.errors {
+        eprintln!("{}", error);
+    }
+    let marked_tiles = snapshot.tiles.iter().flatten().filter(|&&tile| tile == TileType::Marked).count();
+    print!("{}", snapshot);
+    println!("Move heads ticks: {}", statistics.move_heads_ticks);
+    println!("Slide frame ticks: {}", statistics.slide_frame_ticks);
+    println!("Direction changes: {}", statistics.direction_changes);
+    println!("Heads split: {}", statistics.heads_split);
+    println!("Head collisions: {}", statistics.collisions);
+    println!("Heads merged: {}", statistics.merges);
+    let kills: Vec<String> = statistics.heads_killed.iter().map(|(cause, count)| format!("{} {}", count, cause)).collect();
+    println!("Heads killed: {}", if kills.is_empty() { String::from("none") } else { kills.join(", ") });
+    println!("Heads alive: {}", snapshot.heads.len());
+    println!("Marked tiles on the map: {}", marked_tiles);
+    match game_over {
+        Some(game_over) => print!("{}", game_over),
+        None => {
+            println!("The script ended before the game was over");
+            print!("{}", score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::direction_strategy::StrategyKind;
+    use crate::game_over::GameOverReason;
+    use crate::level;
+    use crate::script;
+
+    use super::*;
+
+    // The head can only go up the corridor, whatever the seed
+    const CORRIDOR: &str = "goal_row: 3\nstart: 1,0\n---\n#.#\n#.#\n#.#\n#.#\n";
+
+    #[test]
+    fn test_play_script() {
+        let level = level::parse(CORRIDOR).unwrap();
+        let board_config = BoardConfig {
+            end_conditions: level.header.end_conditions,
+            map: level.into_map_config(0),
+            seed: 0,
+            strategy: StrategyKind::default(),
+            collision_rule: Default::default(),
+            trail_rule: Default::default(),
+        };
+        let script = script::parse("---\ndir up\nmove 2\nslide\nmove 4\n").unwrap();
+
+        let outcome = play(board_config, None, script.events).unwrap();
+        // The map slid once, a generated row came in on top
+        assert_eq!(outcome.snapshot.to_string(), "...\n#@#\n#x#\n#x#\n");
+        // The events left once the game is over are not played
+        assert_eq!(
+            outcome.statistics,
+            Statistics {
+                move_heads_ticks: 3,
+                slide_frame_ticks: 1,
+                direction_changes: 1,
+                ..Statistics::default()
+            }
+        );
+        assert_eq!(outcome.game_over.map(|game_over| game_over.reason), Some(GameOverReason::GoalReached));
+        assert_eq!(outcome.score.total(), 3 * 10 + 3 + 25);
+    }
+
+    #[test]
+    fn test_replay_plays_the_same_game() {
+        let config = GameConfig {
+            map_size: Some((7, 12)),
+            seed: Some(9),
+            record: Some(std::env::temp_dir().join(format!("ruthless_flow_{}.rpl", std::process::id())).to_string_lossy().into_owned()),
+            ..GameConfig::default()
+        };
+        let (_, board_config) = game::load_board_config(&config).unwrap();
+        let recorder = game::open_recorder(&config, &board_config).unwrap();
+        let script = script::parse("---\nmove 4\ndir left\nmove 3\nslide\ndir none\nmove 5\nslide 2\nmove 6\n").unwrap();
+        let played = play(board_config, recorder, script.events).unwrap();
+
+        let replay = Replay::from_file(config.record.as_ref().unwrap()).unwrap();
+        let replay_config = GameConfig {
+            map_size: replay.header.map_size,
+            seed: replay.header.seed,
+            strategy: replay.header.strategy,
+            ..GameConfig::default()
+        };
+        let (_, board_config) = game::load_board_config(&replay_config).unwrap();
+        let events = replay.events.into_iter().map(|(_, event)| event).collect();
+        let replayed = play(board_config, None, events).unwrap();
+
+        assert_eq!(replayed.snapshot, played.snapshot);
+        assert_eq!(replayed.statistics, played.statistics);
+        assert_eq!(replayed.score, played.score);
+        assert_eq!(replayed.game_over, played.game_over);
+        let _ = fs::remove_file(config.record.unwrap());
+    }
+}
@@ -1,13 +1,15 @@
-use enumflags2::{make_bitflags, BitFlag};
-use std::cell::{RefCell, UnsafeCell};
-use std::u8;
+use std::fmt;
+use std::str::FromStr;
 
-use crate::board::BoardEvevents;
 use crate::direction_picker::DirectionPicker;
-use crate::mpsc::Sender;
+use crate::direction_strategy::DirectionStrategy;
+use crate::error::FlowError;
+use crate::snapshot::HeadSnapshot;
+use crate::state_machine::{Hierarchy, Response, StateMachine};
 use crate::map::{Map, TileType};
+use crate::notification::KillCause;
+use crate::save::HeadSave;
 use crate::utils::{Coordinates, Direction, DirectionFlags};
-use rand::{thread_rng, Rng};
 
 
 pub enum HeadEvents<'a, MapType: Map> {
@@ -16,34 +18,111 @@ pub enum HeadEvents<'a, MapType: Map> {
         prohibited_directions : DirectionFlags, // bitfield to hold already explored or forbidden directions
         map: &'a mut MapType,
     },
+    SLIDE_FRAME,
+}
+// Handle on a head. Slots of dead heads are reused by new heads under a new generation.
+// Written `<index>.<generation>`, a lone index standing for the first generation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Id {
+    pub index: u32,
+    pub generation: u32,
+}
+
+impl fmt::Display for Id {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.index, self.generation)
+    }
+}
+
+impl FromStr for Id {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid head id `{}`", text);
+        let (index, generation) = text.split_once('.').unwrap_or((text, "0"));
+        Ok(Id {
+            index: index.parse().map_err(|_| invalid())?,
+            generation: generation.parse().map_err(|_| invalid())?,
+        })
+    }
+}
+
+// What happened to a head during an event. The board applies the outcomes of all its heads
+// within the same tick, once the collisions between them are resolved.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HeadOutcome {
+    // The head moved to a tile that is left for the board to mark
+    HAS_MOVED {
+        from: Coordinates,
+        to: Coordinates,
+        tile: TileType,
+    },
+    // A new head is to be created on the separator the head is leaving
+    HAS_SPLIT {
+        position: Coordinates,
+        coming_from: Direction,
+        parent_direction: Direction,
+    },
+    // The head ran into a marked tile. The board kills it, or merges it into another head
+    HAS_HIT_MARK {
+        position: Coordinates,
+    },
+    HAS_DIED(KillCause),
+    // The head entered a goal tile. The board removes it and counts it as delivered
+    IS_DELIVERED,
+    // The head stayed where it was
+    IS_BLOCKED,
 }
-pub type Id = u32;
 
-#[derive(PartialEq)]
-pub enum HeadAction {
-    HAS_NOT_MOVED,
-    HAS_MOVED(TileType),
+// Alive heads are moving, or about to split after reaching a separator.
+// Dying and delivered heads stay still until the board removes them.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum HeadState {
+    Alive,
+    Moving,
+    Splitting,
+    Dying(KillCause),
+    Delivered,
 }
 
 pub struct SimpleHead {
     id: Id,
+    // Rank of the head among all the heads of the game, the first one being 0
+    birth: u64,
+    // Birth rank of the head the lineage started with. Split heads stay in the lineage of their parent
+    lineage: u64,
+    // Number of heads merged into this one, itself included
+    volume: u64,
+    // Tiles marked by the head and by the heads merged into it
+    marked_tiles: u64,
     position: Coordinates,
     coming_from: Direction,
-    events_sender: Sender<BoardEvevents>,
-    head_split : bool
-
+    picker: DirectionPicker,
+    state: HeadState,
+    // Outcomes of the event being dispatched
+    outcomes: Vec<HeadOutcome>,
 }
 
 pub trait Head: private::Sealed {
     fn new(
         id: Id,
+        birth: u64,
+        lineage: u64,
         position: Coordinates,
         coming_from: Direction,
-        events_sender: Sender<BoardEvevents>,
+        picker: DirectionPicker,
         map : &impl Map
     ) -> Self;
-    fn dispatch(&mut self, event: HeadEvents<impl Map>);
+    fn dispatch(&mut self, event: HeadEvents<impl Map>) -> Result<Vec<HeadOutcome>, FlowError>;
     fn get_id(&mut self) -> Id;
+    fn get_birth(&self) -> u64;
+    fn get_lineage(&self) -> u64;
+    fn get_volume(&self) -> u64;
+    // Count a tile marked by the board after the head moved to it
+    fn tile_marked(&mut self);
+    // Take on the volume and the marked tiles of a head merged into this one
+    fn absorb(&mut self, merged: &Self);
+    fn snapshot(&self) -> HeadSnapshot;
 
 }
 mod private {
@@ -54,14 +133,18 @@ mod private {
         fn set_provenance(&mut self, coming_from: Direction);
         fn get_position(&self) -> Coordinates;
         fn get_provenance(&self) -> Direction;
-        fn move_head_handler(&mut self, direction: Option<Direction>, prohibited_directions : DirectionFlags, map: &mut impl Map);
+        fn move_head_handler(&mut self, direction: Option<Direction>, prohibited_directions : DirectionFlags, map: &mut impl Map, split: bool) -> Result<Response<HeadState, ()>, FlowError>;
+        fn slide_frame_handler(&mut self) -> Result<Response<HeadState, ()>, FlowError>;
+        fn trapped_handler(&mut self, error: FlowError) -> Result<Response<HeadState, ()>, FlowError>;
         fn explore_direction(
             position: Coordinates,
+            coming_from: Direction,
             chosen_direction: Direction,
             prohibited_directions: &mut DirectionFlags,
+            picker: &mut DirectionPicker,
             map: &mut impl Map,
-        ) -> (Direction, TileType, Coordinates);
-        fn move_and_mark_tile(&mut self, map: &mut impl Map, target_position: Coordinates, chosen_direction: Direction);
+        ) -> Result<(Direction, TileType, Coordinates), FlowError>;
+        fn move_to_tile(&mut self, target_position: Coordinates, chosen_direction: Direction);
     }
 }
 
@@ -82,13 +165,12 @@ impl private::Sealed for SimpleHead {
         self.coming_from
     }
 
-    fn move_and_mark_tile(&mut self, map: &mut impl Map, target_position: Coordinates, chosen_direction: Direction) {
-        map.set_tile(target_position, TileType::Marked);
+    fn move_to_tile(&mut self, target_position: Coordinates, chosen_direction: Direction) {
         self.set_position(target_position);
         self.set_provenance(chosen_direction.reverse());
     }
 
-    fn move_head_handler(&mut self, direction: Option<Direction>, mut prohibited_directions : DirectionFlags, map: &mut impl Map) {
+    fn move_head_handler(&mut self, direction: Option<Direction>, mut prohibited_directions : DirectionFlags, map: &mut impl Map, split: bool) -> Result<Response<HeadState, ()>, FlowError> {
 
         // Prevent head from going back to its previous path
         prohibited_directions.insert(self.coming_from); 
@@ -101,157 +183,311 @@ impl private::Sealed for SimpleHead {
                 prohibited_directions.insert(proposed_direction);
             }
             else{ 
-                proposed_direction = DirectionPicker::pick(&mut prohibited_directions);
+                proposed_direction = self.picker.pick(self.coming_from, &mut prohibited_directions)?;
             }
         } else {
-            proposed_direction = DirectionPicker::pick(&mut prohibited_directions);
+            proposed_direction = self.picker.pick(self.coming_from, &mut prohibited_directions)?;
         }
 
         // Try to explore explore the `proposed_direction`. If the move is impossible, explore all the other authorized directions around the head.
-        let (chosen_direction, target_tile, target_position) = Self::explore_direction(self.get_position(), proposed_direction, &mut prohibited_directions, map);
+        let (chosen_direction, target_tile, target_position) = Self::explore_direction(self.get_position(), self.coming_from, proposed_direction, &mut prohibited_directions, &mut self.picker, map)?;
 
-        // Order the board to create a new head if the tile on which we are on a separator
-        if self.head_split {
-            let add_head_event = BoardEvevents::ADD_HEAD {
+        // Ask the board for a new head on the separator we are leaving
+        if split {
+            self.outcomes.push(HeadOutcome::HAS_SPLIT {
                 position: self.get_position(),
                 coming_from: self.get_provenance(),
                 parent_direction: chosen_direction,
-            };
-            self.events_sender.send(add_head_event).unwrap();
-            self.head_split = false;
+            });
         }
 
         //Special action depending on the type of tile we reach
+        let from = self.get_position();
+        let moved = HeadOutcome::HAS_MOVED { from, to: target_position, tile: target_tile };
         match target_tile{
-            // Order the board to kill self
             TileType::Marked => {
-                let remove_head_event = BoardEvevents::KILL_HEAD { id: self.id };
-                self.events_sender.send(remove_head_event).unwrap();
-            }
-            // Move the head to the location and mark the tile
+                self.outcomes.push(HeadOutcome::HAS_HIT_MARK { position: target_position });
+                Ok(Response::Handled(()))
+            },
+            // Move the head to the location. The board marks the tile once it has checked that
+            // no other head moves to it
             TileType::Free =>  {
-                self.move_and_mark_tile(map, target_position, chosen_direction);
+                self.move_to_tile(target_position, chosen_direction);
+                self.outcomes.push(moved);
+                if split {
+                    Ok(Response::Transition(HeadState::Moving, ()))
+                } else {
+                    Ok(Response::Handled(()))
+                }
             },
             TileType::Separator =>  {
-                self.move_and_mark_tile(map, target_position, chosen_direction);
-                self.head_split = true;
+                self.move_to_tile(target_position, chosen_direction);
+                self.outcomes.push(moved);
+                Ok(Response::Transition(HeadState::Splitting, ()))
+            },
+            // The head sinks into the goal, which stays as it is
+            TileType::Goal => {
+                self.move_to_tile(target_position, chosen_direction);
+                self.outcomes.push(moved);
+                Ok(Response::Transition(HeadState::Delivered, ()))
             },
-            TileType::Wall => assert!(true, "Cannot move to a wall"),
+            TileType::Wall => Err(FlowError::WallCollision(target_position)),
+        }
+    }
+
+    fn slide_frame_handler(&mut self) -> Result<Response<HeadState, ()>, FlowError> {
+        // The row the head stands on has been dropped from the map
+        if self.position.y == 0 {
+            Ok(Response::Transition(HeadState::Dying(KillCause::FellOff), ()))
+        }
+        else {
+            self.position.y -= 1;
+            Ok(Response::Handled(()))
+        }
+    }
+
+    fn trapped_handler(&mut self, error: FlowError) -> Result<Response<HeadState, ()>, FlowError> {
+        // Every direction around the head is blocked
+        if error == FlowError::Trapped {
+            self.outcomes.push(HeadOutcome::IS_BLOCKED);
+            Ok(Response::Transition(HeadState::Dying(KillCause::Trapped), ()))
+        } else {
+            Err(error)
         }
-        
     }
 
     fn explore_direction(
         original_position: Coordinates,
+        coming_from: Direction,
         chosen_direction: Direction,
         prohibited_directions: &mut DirectionFlags,
+        picker: &mut DirectionPicker,
         map: &mut impl Map,
-    ) -> (Direction, TileType, Coordinates) {
+    ) -> Result<(Direction, TileType, Coordinates), FlowError> {
 
         if let Some((tile_type, target_position)) = map.get_neighbour_tile(original_position, chosen_direction) {
 
             match tile_type {
-                TileType::Free | TileType::Separator | TileType::Marked => return (chosen_direction, tile_type, target_position),
+                TileType::Free | TileType::Separator | TileType::Marked | TileType::Goal => Ok((chosen_direction, tile_type, target_position)),
                 TileType::Wall => {
-                    let chosen_direction = DirectionPicker::pick(prohibited_directions);
-                    Self::explore_direction(original_position, chosen_direction, prohibited_directions, map)
+                    let chosen_direction = picker.pick(coming_from, prohibited_directions)?;
+                    Self::explore_direction(original_position, coming_from, chosen_direction, prohibited_directions, picker, map)
                 },
             }
         }
         // We are targetting an edge of the map
         else {
-            let chosen_direction = DirectionPicker::pick(prohibited_directions);
-            Self::explore_direction(original_position, chosen_direction, prohibited_directions, map)
+            let chosen_direction = picker.pick(coming_from, prohibited_directions)?;
+            Self::explore_direction(original_position, coming_from, chosen_direction, prohibited_directions, picker, map)
         }
         
     }
 
 }
 
+impl Hierarchy for SimpleHead {
+    type State = HeadState;
+    type Error = FlowError;
+
+    fn state(&self) -> HeadState {
+        self.state
+    }
+
+    fn set_state(&mut self, state: HeadState) {
+        self.state = state;
+    }
+
+    fn parent(state: HeadState) -> Option<HeadState> {
+        match state {
+            HeadState::Moving | HeadState::Splitting => Some(HeadState::Alive),
+            HeadState::Alive | HeadState::Dying(_) | HeadState::Delivered => None,
+        }
+    }
+
+    fn initial(state: HeadState) -> Option<HeadState> {
+        match state {
+            HeadState::Alive => Some(HeadState::Moving),
+            _ => None,
+        }
+    }
+
+    fn entry(&mut self, state: HeadState) -> Result<(), FlowError> {
+        // Ask the board to remove self
+        match state {
+            HeadState::Dying(cause) => self.outcomes.push(HeadOutcome::HAS_DIED(cause)),
+            HeadState::Delivered => self.outcomes.push(HeadOutcome::IS_DELIVERED),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    // There is no coming back from the dead, nor from a goal
+    fn guard(&self, source: HeadState, _target: HeadState) -> bool {
+        !matches!(source, HeadState::Dying(_) | HeadState::Delivered)
+    }
+}
+
+impl<'a, MapType: Map> StateMachine<HeadEvents<'a, MapType>> for SimpleHead {
+    type Output = ();
+
+    fn handle(&mut self, state: HeadState, event: &mut HeadEvents<'a, MapType>) -> Result<Response<HeadState, ()>, FlowError> {
+        match (state, event) {
+            (HeadState::Moving, HeadEvents::MOVE_HEAD { direction, prohibited_directions, map }) => {
+                private::Sealed::move_head_handler(self, *direction, *prohibited_directions, &mut **map, false)
+                    .or_else(|error| private::Sealed::trapped_handler(self, error))
+            }
+            (HeadState::Splitting, HeadEvents::MOVE_HEAD { direction, prohibited_directions, map }) => {
+                private::Sealed::move_head_handler(self, *direction, *prohibited_directions, &mut **map, true)
+                    .or_else(|error| private::Sealed::trapped_handler(self, error))
+            }
+            (HeadState::Alive, HeadEvents::SLIDE_FRAME) => private::Sealed::slide_frame_handler(self),
+            (HeadState::Dying(_) | HeadState::Delivered, HeadEvents::MOVE_HEAD { .. }) => {
+                self.outcomes.push(HeadOutcome::IS_BLOCKED);
+                Ok(Response::Handled(()))
+            }
+            (HeadState::Dying(_) | HeadState::Delivered, HeadEvents::SLIDE_FRAME) => Ok(Response::Handled(())),
+            _ => Ok(Response::Super),
+        }
+    }
+}
+
+impl SimpleHead {
+    pub fn save(&self) -> HeadSave {
+        HeadSave {
+            id: self.id,
+            birth: self.birth,
+            lineage: self.lineage,
+            volume: self.volume,
+            marked_tiles: self.marked_tiles,
+            position: self.position,
+            coming_from: self.coming_from,
+            state: self.state,
+            picker: self.picker.rng_state(),
+        }
+    }
+
+    // The entry actions of the saved state have already run before the head was saved
+    pub fn restore(save: HeadSave, strategy: Box<dyn DirectionStrategy>) -> Self {
+        SimpleHead {
+            id: save.id,
+            birth: save.birth,
+            lineage: save.lineage,
+            volume: save.volume,
+            marked_tiles: save.marked_tiles,
+            position: save.position,
+            coming_from: save.coming_from,
+            picker: DirectionPicker::restore(save.picker, strategy),
+            state: save.state,
+            outcomes: Vec::new(),
+        }
+    }
+}
+
 impl Head for SimpleHead {
     fn new(
         id: Id,
+        birth: u64,
+        lineage: u64,
         position: Coordinates,
         coming_from: Direction,
-        events_sender: Sender<BoardEvevents>,
-        map : &impl Map
+        picker: DirectionPicker,
+        _map : &impl Map
     ) -> SimpleHead { // TODO, initialize with map
-        SimpleHead {
+        let mut head = SimpleHead {
             id,
+            birth,
+            lineage,
+            volume: 1,
+            marked_tiles: 0,
             position,
             coming_from,
-            events_sender,
-            head_split : false
-        }
+            picker,
+            state: HeadState::Alive,
+            outcomes: Vec::new(),
+        };
+        // Entering `Alive` then `Moving` has no action that could fail
+        let _ = head.init();
+        head
     }
 
     fn get_id(&mut self) -> Id{
         self.id
     }
 
-    fn dispatch(&mut self, event: HeadEvents<impl Map>) {
-        match event {
-            HeadEvents::MOVE_HEAD { direction, prohibited_directions, map } => {
-                private::Sealed::move_head_handler(self, direction,prohibited_directions, map)
-            }
-        };
+    fn get_birth(&self) -> u64 {
+        self.birth
+    }
+
+    fn get_lineage(&self) -> u64 {
+        self.lineage
+    }
+
+    fn get_volume(&self) -> u64 {
+        self.volume
+    }
+
+    fn tile_marked(&mut self) {
+        self.marked_tiles += 1;
+    }
+
+    fn absorb(&mut self, merged: &Self) {
+        self.volume += merged.volume;
+        self.marked_tiles += merged.marked_tiles;
+    }
+
+    fn snapshot(&self) -> HeadSnapshot {
+        HeadSnapshot { id: self.id, position: self.position, coming_from: self.coming_from }
+    }
+
+    fn dispatch(&mut self, event: HeadEvents<impl Map>) -> Result<Vec<HeadOutcome>, FlowError> {
+        let result = self.process(event);
+        let outcomes = std::mem::take(&mut self.outcomes);
+        result.map(|()| outcomes)
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use mockall::{predicate, Sequence, mock};
-    use crate::{map::{MockMap}, mpsc::MockSender};
-    use crate::mpsc::SendError;
+    use mockall::Sequence;
+    use crate::map::MockMap;
 
     use super::*;
 
+#[allow(non_snake_case)]
 mod TestConditions{
-use crate::direction_picker::PickerCtx;
-
 use super::*;
 
-pub struct General<'a>{
+pub struct General{
     pub previous_way : Way,
-    pub first_stage: FirstStage<'a>,
-    pub to_wall: Option<ToWall<'a>>,
-    pub on_separator: Option<OnSeparator>,
-    pub last_stage :LastStage
+    pub first_stage: FirstStage,
+    pub to_wall: Option<ToWall>,
 }
 
 
-pub enum FirstStage<'a>{
+pub enum FirstStage{
     ValidDir{way: Way},
-    InvalidDir{way: Way, picker_ctx : &'a PickerCtx},
-}
-
-pub struct OnSeparator{}
-
-pub enum LastStage{
-    ToMarked{id : Id},
-    ToFree
+    InvalidDir{way: Way},
 }
 
 #[derive(Copy, Clone)]
 pub struct Way{pub alt_direction : Direction, pub alt_target_position : Coordinates, pub alt_target_tile : TileType}
 
-pub struct ToWall<'a>{pub ways: Vec<Way> ,pub picker_ctx : &'a PickerCtx}
+pub struct ToWall{pub ways: Vec<Way>}
 
 
 }
 
-fn test_move(seq : & mut Sequence, map: & mut  MockMap, event_sender : &mut MockSender<BoardEvevents>, tc: &TestConditions::General){
+fn test_move(seq : & mut Sequence, map: & mut  MockMap, picker: &mut DirectionPicker, tc: &TestConditions::General){
     let original_position = tc.previous_way.alt_target_position;
-    let original_direction = tc.previous_way.alt_direction;
 
     let mut target_direction;
     let mut target_position;
     let mut target_tile;
 
     match tc.first_stage{
-        TestConditions::FirstStage::InvalidDir { way, picker_ctx } => {
+        TestConditions::FirstStage::InvalidDir { way } => {
         let alt_direction = way.alt_direction;
-        picker_ctx.expect().once().in_sequence(seq).returning(move |_| alt_direction);
+        picker.expect_pick().once().in_sequence(seq).returning(move |_, _| Ok(alt_direction));
         target_direction = way.alt_direction;
         target_tile =  way.alt_target_tile;
         target_position =  way.alt_target_position;
@@ -271,41 +507,13 @@ fn test_move(seq : & mut Sequence, map: & mut  MockMap, event_sender : &mut Mock
             target_direction = way.alt_direction;
             target_tile =  way.alt_target_tile;
             target_position =  way.alt_target_position;
-            to_wall.picker_ctx.expect().once().in_sequence(seq).returning(move |_| target_direction);
+            picker.expect_pick().once().in_sequence(seq).returning(move |_, _| Ok(target_direction));
 
             map.expect_get_neighbour_tile().once().in_sequence(seq)
             .withf(move |position, direction| {print!("{:?}, {:?}",position,direction); *position == original_position && *direction == target_direction} ).
             return_const(Some((target_tile, target_position)));
         }
     }
-
-    if let Some(_) = tc.on_separator{
-        event_sender.expect_send().once().in_sequence(seq).withf(move |board_event| {
-            match board_event{
-            BoardEvevents::ADD_HEAD { position, coming_from, parent_direction} => *position==original_position && *coming_from == original_direction.reverse() && *parent_direction==target_direction ,
-            _ => return false
-        }
-        }).return_const(Result::<(), SendError<BoardEvevents>>::Ok(()));
-    }
-
-    match tc.last_stage {
-        TestConditions::LastStage::ToMarked{id:expected_id} => {
-            event_sender.expect_send().once().in_sequence(seq).withf(move |board_event| {
-                match board_event{
-                BoardEvevents::KILL_HEAD {id} =>  *id == expected_id,
-                _ => return false
-            }}
-            ).return_const(Result::<(), SendError<BoardEvevents>>::Ok(()));
-        },
-        TestConditions::LastStage::ToFree => {
-            map.expect_set_tile().once().in_sequence(seq)
-            .withf(move |position, tile_type| {*position == target_position && *tile_type == TileType::Marked})
-            .return_const(());
-        },
-    };
-
-
-    
 }
 
 
@@ -322,8 +530,7 @@ fn test_basic_moves(){
         
     let mut seq = Sequence::new();
     let mut map = MockMap::default();
-    let mut event_sender = Sender::default();
-    let picker_ctx  = DirectionPicker::pick_context();
+    let mut picker = DirectionPicker::default();
 
     // Test 0, Starting on Free Tile, normal move to free tile with chosen direction accepted
 
@@ -334,12 +541,10 @@ fn test_basic_moves(){
         previous_way: previous_way_0,
         first_stage: TestConditions::FirstStage::ValidDir{way: target_way_0},
         to_wall: None,
-        on_separator: None,
-        last_stage : TestConditions::LastStage::ToFree
     };
 
     // Test 1: Normal move to free tile with chosen direction accepted 
-    test_move(&mut seq, &mut map, &mut event_sender, &tc0);
+    test_move(&mut seq, &mut map, &mut picker, &tc0);
 
     let previous_way_1 = target_way_0;
     let target_way_1 = TestConditions::Way{alt_direction: Direction::Up, alt_target_position : Coordinates{x :11, y:11}, alt_target_tile : TileType::Free};
@@ -349,12 +554,10 @@ fn test_basic_moves(){
         first_stage: TestConditions::FirstStage::ValidDir{
             way: target_way_1},  
         to_wall : None,
-        on_separator: None,
-        last_stage : TestConditions::LastStage::ToFree
     };
 
     // Test 2: Chosen direction refused because it's backward leads to move to free tile
-    test_move(&mut seq, &mut map, &mut event_sender, &tc1);
+    test_move(&mut seq, &mut map, &mut picker, &tc1);
 
     let previous_way_2 = target_way_1;
     let backward_way_2 = target_way_1.alt_direction.reverse();
@@ -362,13 +565,11 @@ fn test_basic_moves(){
     
     let tc2 = TestConditions::General{
         previous_way : previous_way_2,
-        first_stage: TestConditions::FirstStage::InvalidDir{way: target_way_2 , picker_ctx: &picker_ctx},
+        first_stage: TestConditions::FirstStage::InvalidDir{way: target_way_2 },
         to_wall: None,
-        on_separator: None,
-        last_stage : TestConditions::LastStage::ToFree
     };
 
-    test_move(&mut seq, &mut map, &mut event_sender, &tc2);
+    test_move(&mut seq, &mut map, &mut picker, &tc2);
 
     // Test 3: Chosen direction is refused because of a wall 
     let previous_way_3 = target_way_2;
@@ -378,12 +579,9 @@ fn test_basic_moves(){
     let tc3 = TestConditions::General{
         previous_way : previous_way_3,
         first_stage: TestConditions::FirstStage::ValidDir{way: failed_target_way_3},
-        to_wall: Some(TestConditions::ToWall{ways: vec![target_way_3], picker_ctx: &picker_ctx}),
-        on_separator: None,
-        last_stage : TestConditions::LastStage::ToFree
-
+        to_wall: Some(TestConditions::ToWall{ways: vec![target_way_3]}),
     };
-    test_move(&mut seq, &mut map, &mut event_sender, &tc3);
+    test_move(&mut seq, &mut map, &mut picker, &tc3);
     
 
     // Test 4: Chosen direction refused because it's empty leads to move to Separator tile
@@ -392,12 +590,10 @@ fn test_basic_moves(){
     
     let tc4 = TestConditions::General{
         previous_way : previous_way_4,
-        first_stage: TestConditions::FirstStage::InvalidDir{way: target_way_4 , picker_ctx: &picker_ctx},
+        first_stage: TestConditions::FirstStage::InvalidDir{way: target_way_4 },
         to_wall: None,
-        on_separator: None,
-        last_stage : TestConditions::LastStage::ToFree
     };
-    test_move(&mut seq, &mut map, &mut event_sender, &tc4);
+    test_move(&mut seq, &mut map, &mut picker, &tc4);
 
     // Test 5: Chosen direction leads to free tile
     let previous_way_5 = target_way_4;
@@ -407,40 +603,105 @@ fn test_basic_moves(){
         previous_way : previous_way_5,
         first_stage: TestConditions::FirstStage::ValidDir { way: target_way_5},
         to_wall: None,
-        on_separator: Some(TestConditions::OnSeparator{}),
-        last_stage : TestConditions::LastStage::ToFree
     };
-    test_move(&mut seq, &mut map, &mut event_sender, &tc5);
+    test_move(&mut seq, &mut map, &mut picker, &tc5);
 
     // Test 6: Chosen direction leads to marked tile and then to merge
     let previous_way_6 = target_way_5;
     let target_way_6 = TestConditions::Way{alt_direction: Direction::Down, alt_target_position : Coordinates{x :8, y:9}, alt_target_tile : TileType::Marked};
-    let head_id = 524;
+    let head_id = Id{index: 524, generation: 2};
     let tc6 = TestConditions::General{
         previous_way : previous_way_6,
         first_stage: TestConditions::FirstStage::ValidDir { way: target_way_6},
-        on_separator: None,
         to_wall: None,
-        last_stage : TestConditions::LastStage::ToMarked{id:head_id},
     };
-    test_move(&mut seq, &mut map, &mut event_sender, &tc6);
+    test_move(&mut seq, &mut map, &mut picker, &tc6);
 
-    let mut simple_head = SimpleHead::new(head_id, previous_way_0.alt_target_position, previous_way_0.alt_direction,  event_sender, &mut map);
+    let mut simple_head = SimpleHead::new(head_id, 0, 0, previous_way_0.alt_target_position, previous_way_0.alt_direction, picker, &map);
 
-    dispatch_head_evt(Some(target_way_0.alt_direction), &mut map, &mut simple_head);
+    let moved = |from: TestConditions::Way, to: TestConditions::Way| HeadOutcome::HAS_MOVED {
+        from: from.alt_target_position,
+        to: to.alt_target_position,
+        tile: to.alt_target_tile,
+    };
+    assert_eq!(dispatch_head_evt(Some(target_way_0.alt_direction), &mut map, &mut simple_head), vec![moved(previous_way_0, target_way_0)]);
     dispatch_head_evt(Some(target_way_1.alt_direction), &mut map, &mut simple_head);
     dispatch_head_evt(Some(backward_way_2), &mut map, &mut simple_head);
     dispatch_head_evt(Some(failed_target_way_3.alt_direction), &mut map, &mut simple_head);
-    dispatch_head_evt(None, &mut map, &mut simple_head);
-    dispatch_head_evt(Some(target_way_5.alt_direction), &mut map, &mut simple_head);
-    dispatch_head_evt(Some(target_way_6.alt_direction), &mut map, &mut simple_head);
+    assert_eq!(dispatch_head_evt(None, &mut map, &mut simple_head), vec![moved(previous_way_4, target_way_4)]);
+
+    // Leaving the separator asks for a new head where the head stood
+    let split = HeadOutcome::HAS_SPLIT {
+        position: target_way_4.alt_target_position,
+        coming_from: target_way_4.alt_direction.reverse(),
+        parent_direction: target_way_5.alt_direction,
+    };
+    assert_eq!(dispatch_head_evt(Some(target_way_5.alt_direction), &mut map, &mut simple_head), vec![split, moved(previous_way_5, target_way_5)]);
+    assert_eq!(
+        dispatch_head_evt(Some(target_way_6.alt_direction), &mut map, &mut simple_head),
+        vec![HeadOutcome::HAS_HIT_MARK { position: target_way_6.alt_target_position }]
+    );
+}
+
+#[test]
+fn test_slide_frame(){
+    let map = MockMap::default();
+    let head_id = Id{index: 3, generation: 0};
+    let mut simple_head = SimpleHead::new(head_id, 0, 0, Coordinates{x: 2, y: 1}, Direction::Down, DirectionPicker::default(), &map);
+
+    assert_eq!(simple_head.dispatch(HeadEvents::<MockMap>::SLIDE_FRAME), Ok(vec![]));
+    assert_eq!(simple_head.position, Coordinates{x: 2, y: 0});
+
+    // Only the head standing on the dropped row dies
+    assert_eq!(simple_head.dispatch(HeadEvents::<MockMap>::SLIDE_FRAME), Ok(vec![HeadOutcome::HAS_DIED(KillCause::FellOff)]));
+    assert_eq!(simple_head.state, HeadState::Dying(KillCause::FellOff));
+
+    // Dying heads wait to be removed without dying again
+    assert_eq!(simple_head.dispatch(HeadEvents::<MockMap>::SLIDE_FRAME), Ok(vec![]));
+    assert_eq!(simple_head.state, HeadState::Dying(KillCause::FellOff));
+}
 
+#[test]
+fn test_trapped(){
+    let mut map = MockMap::default();
+    let mut picker = DirectionPicker::default();
+    let position = Coordinates{x: 4, y: 4};
 
+    // Surrounded by walls: the picker runs out of directions to explore
+    map.expect_get_neighbour_tile().times(2).return_const(Some((TileType::Wall, position)));
+    let mut picks = vec![Ok(Direction::Left), Ok(Direction::Right), Err(FlowError::Trapped)];
+    picker.expect_pick().times(3).returning(move |_, _| picks.remove(0));
+
+    let mut simple_head = SimpleHead::new(Id{index: 0, generation: 0}, 0, 0, position, Direction::Down, picker, &map);
+    let event = HeadEvents::MOVE_HEAD { direction: None, prohibited_directions : DirectionFlags::empty(), map: &mut map};
+    assert_eq!(simple_head.dispatch(event), Ok(vec![HeadOutcome::IS_BLOCKED, HeadOutcome::HAS_DIED(KillCause::Trapped)]));
+}
+
+#[test]
+fn test_delivered(){
+    let mut map = MockMap::default();
+    let mut picker = DirectionPicker::default();
+    let from = Coordinates{x: 2, y: 2};
+    let goal = Coordinates{x: 2, y: 3};
+
+    map.expect_get_neighbour_tile().times(1).return_const(Some((TileType::Goal, goal)));
+    picker.expect_pick().times(1).return_const(Ok(Direction::Up));
+
+    let mut simple_head = SimpleHead::new(Id{index: 0, generation: 0}, 0, 0, from, Direction::Down, picker, &map);
+    assert_eq!(dispatch_head_evt(None, &mut map, &mut simple_head), vec![
+        HeadOutcome::HAS_MOVED { from, to: goal, tile: TileType::Goal },
+        HeadOutcome::IS_DELIVERED,
+    ]);
+    assert_eq!(simple_head.state, HeadState::Delivered);
+    assert_eq!(simple_head.position, goal);
+
+    // A delivered head moves no more
+    assert_eq!(dispatch_head_evt(Some(Direction::Up), &mut map, &mut simple_head), vec![HeadOutcome::IS_BLOCKED]);
 }
 
-fn dispatch_head_evt(head_going_to: Option<Direction>, map: &mut MockMap, simple_head: &mut SimpleHead) {
-    let event = HeadEvents::MOVE_HEAD { direction: head_going_to, prohibited_directions : DirectionFlags::empty(),  map: map};
-    simple_head.dispatch(event);
+fn dispatch_head_evt(head_going_to: Option<Direction>, map: &mut MockMap, simple_head: &mut SimpleHead) -> Vec<HeadOutcome> {
+    let event = HeadEvents::MOVE_HEAD { direction: head_going_to, prohibited_directions : DirectionFlags::empty(),  map};
+    simple_head.dispatch(event).unwrap()
 }
     
 }
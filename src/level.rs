@@ -0,0 +1,396 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::collision::{CollisionRule, TrailRule};
+use crate::direction_strategy::StrategyKind;
+use crate::game_over::EndConditions;
+use crate::map::{MapConfig, TileType};
+use crate::utils::Coordinates;
+
+// Level files are made of an optional header of `key: value` lines, closed by a `---` line,
+// followed by the tile grid. The first grid line is the top of the map, the last one is row 0.
+// Header lines starting with `;` are comments. Besides the end of the game when all heads are
+// dead, the header can set the goal row or tile, the distance to scroll, the ticks to survive or
+// the number of heads to deliver to the goal tiles (`o` in the grid).
+pub const HEADER_END: &str = "---";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LevelHeader {
+    pub title: Option<String>,
+    pub start: Option<Coordinates>,
+    pub move_heads_period_ms: Option<i64>,
+    pub slide_frame_period_ms: Option<i64>,
+    pub strategy: Option<StrategyKind>,
+    // What happens to heads moving to the same tile
+    pub collision: Option<CollisionRule>,
+    // What happens to heads moving to a marked tile
+    pub trail: Option<TrailRule>,
+    pub end_conditions: EndConditions,
+}
+
+// Header lines, without the closing `---`
+impl fmt::Display for LevelHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(title) = &self.title {
+            writeln!(f, "title: {}", title)?;
+        }
+        if let Some(start) = self.start {
+            writeln!(f, "start: {},{}", start.x, start.y)?;
+        }
+        if let Some(period) = self.move_heads_period_ms {
+            writeln!(f, "move_heads_period: {}", period)?;
+        }
+        if let Some(period) = self.slide_frame_period_ms {
+            writeln!(f, "slide_frame_period: {}", period)?;
+        }
+        if let Some(strategy) = self.strategy {
+            writeln!(f, "strategy: {}", strategy)?;
+        }
+        if let Some(collision) = self.collision {
+            writeln!(f, "collision: {}", collision)?;
+        }
+        if let Some(trail) = self.trail {
+            writeln!(f, "trail: {}", trail)?;
+        }
+
+        let conditions = &self.end_conditions;
+        if let Some(goal_row) = conditions.goal_row {
+            writeln!(f, "goal_row: {}", goal_row)?;
+        }
+        if let Some(goal) = conditions.goal_tile {
+            writeln!(f, "goal: {},{}", goal.x, goal.y)?;
+        }
+        if let Some(distance) = conditions.distance {
+            writeln!(f, "distance: {}", distance)?;
+        }
+        if let Some(ticks) = conditions.survive_ticks {
+            writeln!(f, "survive_ticks: {}", ticks)?;
+        }
+        if let Some(heads) = conditions.deliver {
+            writeln!(f, "deliver: {}", heads)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Level {
+    pub header: LevelHeader,
+    pub rows: VecDeque<Vec<TileType>>,
+}
+
+impl Level {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LevelError> {
+        parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn into_map_config(self, seed: u64) -> MapConfig {
+        MapConfig::from_rows(self.rows, self.header.start, seed)
+    }
+}
+
+#[derive(Debug)]
+pub enum LevelError {
+    Io(io::Error),
+    Syntax {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+}
+
+impl LevelError {
+    fn syntax(line: usize, column: usize, message: impl Into<String>) -> Self {
+        LevelError::Syntax {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for LevelError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LevelError::Io(error) => write!(f, "Cannot read level: {}", error),
+            LevelError::Syntax { line, column, message } => {
+                write!(f, "Line {}, column {}: {}", line, column, message)
+            }
+        }
+    }
+}
+
+impl From<io::Error> for LevelError {
+    fn from(error: io::Error) -> Self {
+        LevelError::Io(error)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Level, LevelError> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let (header, grid_start) = match lines.iter().position(|line| line.trim_end() == HEADER_END) {
+        Some(header_end) => (parse_header(&lines[..header_end])?, header_end + 1),
+        None => (LevelHeader::default(), 0),
+    };
+
+    let rows = parse_grid(&lines, grid_start)?;
+
+    if let Some(start) = header.start {
+        if start.x >= rows[0].len() || start.y >= rows.len() {
+            let line_number = header_line(&lines, "start").unwrap_or(1);
+            return Err(LevelError::syntax(line_number, 1, "Start position is out of the map"));
+        }
+    }
+    // The goal row may be above the map, it will come into view as the map slides
+    if let Some(goal) = header.end_conditions.goal_tile {
+        if goal.x >= rows[0].len() {
+            let line_number = header_line(&lines, "goal").unwrap_or(1);
+            return Err(LevelError::syntax(line_number, 1, "Goal is out of the map"));
+        }
+    }
+
+    Ok(Level { header, rows })
+}
+
+// Tile grid made of `lines` from `grid_start` on, returned from the bottom row to the top one
+pub fn parse_grid(lines: &[&str], grid_start: usize) -> Result<VecDeque<Vec<TileType>>, LevelError> {
+    // Trailing blank lines are not part of the grid
+    let mut grid_end = lines.len();
+    while grid_end > grid_start && lines[grid_end - 1].trim_end().is_empty() {
+        grid_end -= 1;
+    }
+    if grid_end == grid_start {
+        return Err(LevelError::syntax(grid_start + 1, 1, "The level has no tiles"));
+    }
+
+    let mut rows = VecDeque::new();
+    let mut length = None;
+    for (index, line) in lines.iter().enumerate().take(grid_end).skip(grid_start) {
+        let line_number = index + 1;
+        let row = parse_row(line.trim_end(), line_number)?;
+
+        match length {
+            None => length = Some(row.len()),
+            Some(length) if length != row.len() => {
+                return Err(LevelError::syntax(
+                    line_number,
+                    row.len().min(length) + 1,
+                    format!("Row has {} tiles but the previous ones have {}", row.len(), length),
+                ))
+            }
+            _ => (),
+        }
+
+        // Rows are listed from top to bottom
+        rows.push_front(row);
+    }
+
+    Ok(rows)
+}
+
+// Grid lines of `rows`, top row first, as read by `parse_grid`
+pub fn format_grid(rows: &VecDeque<Vec<TileType>>) -> String {
+    let mut grid = String::new();
+    for row in rows.iter().rev() {
+        grid.extend(row.iter().map(|tile| tile.to_char()));
+        grid.push('\n');
+    }
+    grid
+}
+
+fn parse_row(line: &str, line_number: usize) -> Result<Vec<TileType>, LevelError> {
+    if line.is_empty() {
+        return Err(LevelError::syntax(line_number, 1, "Empty row"));
+    }
+
+    line.chars()
+        .enumerate()
+        .map(|(column, c)| {
+            TileType::from_char(c).ok_or_else(|| {
+                LevelError::syntax(line_number, column + 1, format!("Unknown tile `{}`", c))
+            })
+        })
+        .collect()
+}
+
+fn parse_header(lines: &[&str]) -> Result<LevelHeader, LevelError> {
+    let mut header = LevelHeader::default();
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let entry = match split_header_line(line, line_number)? {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if !parse_header_entry(&mut header, &entry)? {
+            return Err(entry.unknown_key());
+        }
+    }
+
+    Ok(header)
+}
+
+// `key: value` line of a header, along with where its value starts for error messages
+pub struct HeaderEntry<'a> {
+    pub key: &'a str,
+    pub value: &'a str,
+    pub line: usize,
+    pub key_column: usize,
+    pub value_column: usize,
+}
+
+impl HeaderEntry<'_> {
+    pub fn unknown_key(&self) -> LevelError {
+        LevelError::syntax(self.line, self.key_column, format!("Unknown header key `{}`", self.key))
+    }
+
+    pub fn invalid_value(&self, message: impl Into<String>) -> LevelError {
+        LevelError::syntax(self.line, self.value_column, message)
+    }
+}
+
+// Blank lines and comments have no entry
+pub fn split_header_line(line: &str, line_number: usize) -> Result<Option<HeaderEntry<'_>>, LevelError> {
+    let line = line.trim_end();
+    if line.trim_start().is_empty() || line.trim_start().starts_with(';') {
+        return Ok(None);
+    }
+
+    let (key, value) = line
+        .split_once(':')
+        .ok_or_else(|| LevelError::syntax(line_number, 1, "Expected a `key: value` pair"))?;
+    Ok(Some(HeaderEntry {
+        key: key.trim(),
+        value: value.trim(),
+        line: line_number,
+        key_column: line.len() - line.trim_start().len() + 1,
+        value_column: key.len() + 2 + (value.len() - value.trim_start().len()),
+    }))
+}
+
+// Fill `header` with a level header entry. Returns false if the key is not part of the level format
+pub fn parse_header_entry(header: &mut LevelHeader, entry: &HeaderEntry) -> Result<bool, LevelError> {
+    let (value, line_number, value_column) = (entry.value, entry.line, entry.value_column);
+
+    match entry.key {
+        "title" => header.title = Some(value.to_string()),
+        "start" => header.start = Some(parse_coordinates(value, line_number, value_column)?),
+        "move_heads_period" => {
+            header.move_heads_period_ms = Some(parse_period(value, line_number, value_column)?)
+        }
+        "slide_frame_period" => {
+            header.slide_frame_period_ms = Some(parse_period(value, line_number, value_column)?)
+        }
+        "strategy" => {
+            let strategy = value
+                .parse()
+                .map_err(|message: String| LevelError::syntax(line_number, value_column, message))?;
+            header.strategy = Some(strategy)
+        }
+        "collision" => {
+            let collision = value
+                .parse()
+                .map_err(|message: String| LevelError::syntax(line_number, value_column, message))?;
+            header.collision = Some(collision)
+        }
+        "trail" => {
+            let trail = value
+                .parse()
+                .map_err(|message: String| LevelError::syntax(line_number, value_column, message))?;
+            header.trail = Some(trail)
+        }
+        "goal_row" => header.end_conditions.goal_row = Some(parse_count(value, line_number, value_column)? as usize),
+        "goal" => header.end_conditions.goal_tile = Some(parse_coordinates(value, line_number, value_column)?),
+        "distance" => header.end_conditions.distance = Some(parse_count(value, line_number, value_column)?),
+        "survive_ticks" => header.end_conditions.survive_ticks = Some(parse_count(value, line_number, value_column)?),
+        "deliver" => header.end_conditions.deliver = Some(parse_count(value, line_number, value_column)?),
+        _ => return Ok(false),
+    }
+
+    Ok(true)
+}
+
+pub fn parse_coordinates(value: &str, line: usize, column: usize) -> Result<Coordinates, LevelError> {
+    let parse_value = |text: &str| text.trim().parse::<usize>().ok();
+    match value.split_once(',') {
+        Some((x, y)) => match (parse_value(x), parse_value(y)) {
+            (Some(x), Some(y)) => Ok(Coordinates { x, y }),
+            _ => Err(LevelError::syntax(line, column, format!("Invalid coordinates `{}`", value))),
+        },
+        None => Err(LevelError::syntax(line, column, "Expected coordinates as `x,y`")),
+    }
+}
+
+fn parse_period(value: &str, line: usize, column: usize) -> Result<i64, LevelError> {
+    match value.parse::<i64>() {
+        Ok(period) if period > 0 => Ok(period),
+        _ => Err(LevelError::syntax(
+            line,
+            column,
+            format!("Invalid tick period `{}`, expected a positive number of milliseconds", value),
+        )),
+    }
+}
+
+pub fn parse_count(value: &str, line: usize, column: usize) -> Result<u64, LevelError> {
+    value
+        .parse::<u64>()
+        .map_err(|_| LevelError::syntax(line, column, format!("Invalid number `{}`", value)))
+}
+
+fn header_line(lines: &[&str], key: &str) -> Option<usize> {
+    lines
+        .iter()
+        .position(|line| line.split_once(':').map(|(k, _)| k.trim()) == Some(key))
+        .map(|index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::direction_strategy::Side;
+
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        let level = parse("title: Tutorial\nstart: 1,0\nmove_heads_period: 250\nstrategy: hug left\ncollision: merge\ntrail: merge\ngoal: 2,6\ndistance: 4\ndeliver: 3\n---\n#+x\n.o.\n").unwrap();
+
+        assert_eq!(level.header.title.as_deref(), Some("Tutorial"));
+        assert_eq!(level.header.start, Some(Coordinates { x: 1, y: 0 }));
+        assert_eq!(level.header.move_heads_period_ms, Some(250));
+        assert_eq!(level.header.slide_frame_period_ms, None);
+        assert_eq!(level.header.strategy, Some(StrategyKind::WallHugging(Side::Left)));
+        assert_eq!(level.header.collision, Some(CollisionRule::Merge));
+        assert_eq!(level.header.trail, Some(TrailRule::Merge));
+        assert_eq!(level.header.end_conditions.goal_tile, Some(Coordinates { x: 2, y: 6 }));
+        assert_eq!(level.header.end_conditions.distance, Some(4));
+        assert_eq!(level.header.end_conditions.survive_ticks, None);
+        assert_eq!(level.header.end_conditions.deliver, Some(3));
+        assert_eq!(level.rows[0], vec![TileType::Free, TileType::Goal, TileType::Free]);
+        assert_eq!(level.rows[1], vec![TileType::Wall, TileType::Separator, TileType::Marked]);
+
+        let text = format!("{}{}\n{}", level.header, HEADER_END, format_grid(&level.rows));
+        let written = parse(&text).unwrap();
+        assert_eq!(written.header, level.header);
+        assert_eq!(written.rows, level.rows);
+    }
+
+    #[test]
+    fn test_parse_errors_location() {
+        let error_location = |text: &str| match parse(text) {
+            Err(LevelError::Syntax { line, column, .. }) => (line, column),
+            _ => panic!("Expected a syntax error for {:?}", text),
+        };
+
+        assert_eq!(error_location("...\n.?.\n"), (2, 2));
+        assert_eq!(error_location("title: A\n---\n...\n..\n"), (4, 3));
+        assert_eq!(error_location("title: A\nspeed: 3\n---\n...\n"), (2, 1));
+        assert_eq!(error_location("start: 1;2\n---\n...\n"), (1, 8));
+        assert_eq!(error_location("start: 5,0\n---\n...\n"), (1, 1));
+        assert_eq!(error_location("title: A\ngoal: 3,9\n---\n...\n"), (2, 1));
+        assert_eq!(error_location("distance: far\n---\n...\n"), (1, 11));
+    }
+}
@@ -1,11 +1,44 @@
+#![allow(non_camel_case_types)]
+
 mod board;
+mod collision;
 mod heads;
+mod level;
 mod map;
+mod row_generator;
+mod save;
+mod snapshot;
 mod state_machine;
 mod utils;
 mod direction_picker;
+mod direction_strategy;
+mod error;
 mod head_list;
-mod mpsc;
+mod notification;
+mod renderer;
+mod piston_renderer;
+#[cfg(feature = "tui")]
+mod tui_renderer;
+mod game_over;
+mod replay;
+mod score;
+mod script;
+mod app;
+mod game;
+mod headless;
+
 fn main() {
-    //let board = board::SimpleBoard::new();
+    let config = match game::GameConfig::from_args(std::env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{}", message);
+            eprintln!("{}", game::USAGE);
+            std::process::exit(1);
+        }
+    };
+
+    if let Err(message) = game::run(config) {
+        eprintln!("{}", message);
+        std::process::exit(1);
+    }
 }
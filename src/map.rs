@@ -1,6 +1,9 @@
-use mockall::automock;
-use std::{collections::VecDeque, iter::Cycle, result::Iter, slice};
+use std::{collections::VecDeque, path::Path};
 
+use crate::error::FlowError;
+use crate::level::{self, Level, LevelError};
+use crate::row_generator::{RowGenerator, SeededRowGenerator};
+use crate::save::{GeneratorState, SavableMap};
 use crate::utils::{Coordinates, Direction};
 
 #[derive(Copy, Clone, Debug, PartialEq)]
@@ -9,13 +12,38 @@ pub enum TileType {
     Free,
     Separator,
     Wall,
+    // Sink delivering the heads entering it. It is never marked
+    Goal,
+}
+
+impl TileType {
+    pub fn from_char(c: char) -> Option<TileType> {
+        match c {
+            'x' => Some(TileType::Marked),
+            '.' => Some(TileType::Free),
+            '+' => Some(TileType::Separator),
+            '#' => Some(TileType::Wall),
+            'o' => Some(TileType::Goal),
+            _ => None,
+        }
+    }
+
+    pub fn to_char(self) -> char {
+        match self {
+            TileType::Marked => 'x',
+            TileType::Free => '.',
+            TileType::Separator => '+',
+            TileType::Wall => '#',
+            TileType::Goal => 'o',
+        }
+    }
 }
 
 #[cfg_attr(test, mockall::automock)]
 pub trait Map {
-    fn new() -> Self;
-    fn set_tile(&mut self, position: Coordinates, tile_type: TileType);
-    fn get_tile(&mut self, position: Coordinates) -> TileType;
+    fn new(config: MapConfig) -> Self;
+    fn set_tile(&mut self, position: Coordinates, tile_type: TileType) -> Result<(), FlowError>;
+    fn get_tile(&self, position: Coordinates) -> Result<TileType, FlowError>;
     fn get_neighbour_tile(
         &mut self,
         position: Coordinates,
@@ -23,54 +51,129 @@ pub trait Map {
     ) -> Option<(TileType, Coordinates)>;
     fn get_length(&self) -> usize;
     fn get_height(&self) -> usize;
+    // Drop the bottom row of the map and append a new one on top
+    fn slide(&mut self);
+}
+
+pub enum MapContents {
+    // Initial rows, from the bottom of the map to its top
+    Rows(VecDeque<Vec<TileType>>),
+    // Let the row generator build the whole map
+    Generated,
+}
+
+// Configs are checked where they are read: level files have equally long rows and a start position
+// inside the map, map sizes given on the command line are not empty. Maps built from anything else
+// panic rather than start broken
+pub struct MapConfig {
+    pub width: usize,
+    pub height: usize,
+    pub contents: MapContents,
+    pub spawn_point: Coordinates,
+    // Seed of the generator building the rows appended when the map slides
+    pub seed: u64,
+}
+
+impl MapConfig {
+    pub fn generated(width: usize, height: usize, seed: u64) -> Self {
+        MapConfig {
+            width,
+            height,
+            contents: MapContents::Generated,
+            spawn_point: Coordinates { x: width / 2, y: 0 },
+            seed,
+        }
+    }
+
+    pub fn from_rows(rows: VecDeque<Vec<TileType>>, spawn_point: Option<Coordinates>, seed: u64) -> Self {
+        let width = rows.front().map_or(0, |row| row.len());
+        MapConfig {
+            width,
+            height: rows.len(),
+            contents: MapContents::Rows(rows),
+            spawn_point: spawn_point.unwrap_or(Coordinates { x: width / 2, y: 0 }),
+            seed,
+        }
+    }
+}
+
+// Map size written as `<width>x<height>`
+pub fn parse_size(text: &str) -> Result<(usize, usize), String> {
+    let parse_dimension = |text: &str| text.parse::<usize>().ok().filter(|&dimension| dimension > 0);
+    match text.split_once('x').map(|(width, height)| (parse_dimension(width), parse_dimension(height))) {
+        Some((Some(width), Some(height))) => Ok((width, height)),
+        _ => Err(format!("Invalid map size `{}`, expected <width>x<height>", text)),
+    }
 }
 
 pub struct SimpleMap {
     pub sto: VecDeque<Vec<TileType>>,
+    generator: SeededRowGenerator,
 }
-impl SimpleMap {}
+// The game reads levels through `Level`, which also gives it their header. These build the map straight
+// from a level, keeping the line and column of its errors
+#[allow(dead_code)]
+impl SimpleMap {
+    // The seed drives the rows appended when the map slides
+    pub fn from_str_seeded(text: &str, seed: u64) -> Result<Self, LevelError> {
+        Ok(SimpleMap::new(level::parse(text)?.into_map_config(seed)))
+    }
+
+    pub fn from_file(path: impl AsRef<Path>, seed: u64) -> Result<Self, LevelError> {
+        Ok(SimpleMap::new(Level::from_file(path)?.into_map_config(seed)))
+    }
+}
+
 impl Map for SimpleMap {
-    fn new() -> Self {
+    fn new(config: MapConfig) -> Self {
+        assert!(config.width > 0 && config.height > 0, "Map cannot be empty");
+        assert!(
+            config.spawn_point.x < config.width && config.spawn_point.y < config.height,
+            "Spawn point is out of the map"
+        );
+
+        let mut generator = SeededRowGenerator::new(config.seed);
+        let sto = match config.contents {
+            MapContents::Rows(rows) => {
+                assert!(
+                    rows.len() == config.height && rows.iter().all(|row| row.len() == config.width),
+                    "Map rows do not match the configured dimensions"
+                );
+                rows
+            }
+            MapContents::Generated => {
+                // The bottom row is left free for the flow to start on
+                let mut rows = VecDeque::from([vec![TileType::Free; config.width]]);
+                while rows.len() < config.height {
+                    let new_row = generator.next_row(&rows[rows.len() - 1]);
+                    rows.push_back(new_row);
+                }
+                rows
+            }
+        };
+
         SimpleMap {
-            sto: VecDeque::from([
-                vec![
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                ],
-                vec![
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                ],
-                vec![
-                    TileType::Free,
-                    TileType::Wall,
-                    TileType::Wall,
-                    TileType::Wall,
-                    TileType::Free,
-                ],
-                vec![
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                    TileType::Free,
-                ],
-            ]),
+            sto,
+            generator,
         }
     }
 
-    fn set_tile(&mut self, position: Coordinates, tile_type: TileType) {
-        self.sto[position.y][position.x] = tile_type;
+    fn set_tile(&mut self, position: Coordinates, tile_type: TileType) -> Result<(), FlowError> {
+        let tile = self
+            .sto
+            .get_mut(position.y)
+            .and_then(|row| row.get_mut(position.x))
+            .ok_or(FlowError::OutOfBounds(position))?;
+        *tile = tile_type;
+        Ok(())
     }
 
-    fn get_tile(&mut self, position: Coordinates) -> TileType {
-        self.sto[position.y][position.x]
+    fn get_tile(&self, position: Coordinates) -> Result<TileType, FlowError> {
+        self.sto
+            .get(position.y)
+            .and_then(|row| row.get(position.x))
+            .copied()
+            .ok_or(FlowError::OutOfBounds(position))
     }
     fn get_neighbour_tile(
         &mut self,
@@ -100,23 +203,94 @@ impl Map for SimpleMap {
             && x >= 0
             && y >= 0
         {
-            let tile_type = self.sto[y as usize][x as usize];
             let position = Coordinates {
                 x: x as usize,
                 y: y as usize,
             };
 
-            Some((tile_type, position))
+            Some((self.get_tile(position).ok()?, position))
         } else {
             None
         }
     }
 
     fn get_length(&self) -> usize {
-        self.sto.len()
+        self.sto[0].len()
     }
 
     fn get_height(&self) -> usize {
-        self.sto[0].len()
+        self.sto.len()
+    }
+
+    fn slide(&mut self) {
+        // The new row follows the current top row, which is also the bottom one on a single row map
+        let new_row = self.generator.next_row(&self.sto[self.sto.len() - 1]);
+        self.sto.pop_front();
+        self.sto.push_back(new_row);
+    }
+}
+
+impl SavableMap for SimpleMap {
+    fn generator_state(&self) -> GeneratorState {
+        self.generator.state()
+    }
+
+    fn restore(&mut self, rows: VecDeque<Vec<TileType>>, generator: GeneratorState) {
+        assert!(!rows.is_empty() && !rows[0].is_empty(), "Map cannot be empty");
+        self.sto = rows;
+        self.generator.restore(generator);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_new_from_config() {
+        let rows = VecDeque::from([
+            vec![TileType::Free, TileType::Free, TileType::Free],
+            vec![TileType::Wall, TileType::Separator, TileType::Free],
+        ]);
+        let mut map = SimpleMap::new(MapConfig::from_rows(rows, None, 0));
+        assert_eq!((map.get_length(), map.get_height()), (3, 2));
+        assert_eq!(map.get_tile(Coordinates { x: 1, y: 1 }), Ok(TileType::Separator));
+        let outside = Coordinates { x: 3, y: 0 };
+        assert_eq!(map.set_tile(outside, TileType::Marked), Err(FlowError::OutOfBounds(outside)));
+        assert_eq!(
+            map.get_neighbour_tile(Coordinates { x: 2, y: 0 }, Direction::Up),
+            Some((TileType::Free, Coordinates { x: 2, y: 1 }))
+        );
+        assert_eq!(map.get_neighbour_tile(Coordinates { x: 2, y: 0 }, Direction::Right), None);
+
+        let mut map = SimpleMap::new(MapConfig::generated(7, 12, 3));
+        assert_eq!((map.get_length(), map.get_height()), (7, 12));
+        assert!(map.sto[0].iter().all(|tile| *tile == TileType::Free));
+
+        map.slide();
+        assert_eq!((map.get_length(), map.get_height()), (7, 12));
+
+        let mut map = SimpleMap::new(MapConfig::generated(5, 1, 3));
+        map.slide();
+        assert_eq!((map.get_length(), map.get_height()), (5, 1));
+    }
+
+    #[test]
+    fn test_from_level() {
+        let mut first = SimpleMap::from_str_seeded("start: 0,1\n---\n#..\n...\n", 4).unwrap();
+        let mut second = SimpleMap::from_str_seeded("#..\n...\n", 4).unwrap();
+        assert_eq!(first.get_tile(Coordinates { x: 0, y: 1 }), Ok(TileType::Wall));
+
+        for _ in 0..5 {
+            first.slide();
+            second.slide();
+            assert_eq!(first.sto, second.sto);
+        }
+
+        match SimpleMap::from_str_seeded("...\n.?.\n", 4) {
+            Err(LevelError::Syntax { line, column, .. }) => assert_eq!((line, column), (2, 2)),
+            _ => panic!("Expected a syntax error"),
+        }
+        assert!(matches!(SimpleMap::from_file("levels/missing.lvl", 4), Err(LevelError::Io(_))));
     }
 }
@@ -0,0 +1,108 @@
+use std::sync::mpsc;
+
+use crate::collision::CollisionRule;
+use crate::error::BoardError;
+use crate::game_over::GameOver;
+use crate::heads::Id;
+use crate::utils::Coordinates;
+
+// Why a head has been removed from the board
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum KillCause {
+    // The head ran into a marked tile
+    Collision,
+    // The row the head stood on slid out of the map
+    FellOff,
+    // Every direction around the head was blocked
+    Trapped,
+    // Another head took the tile the head was moving to
+    Clash,
+    // The head went on as part of another one moving to the same tile
+    Merged,
+    // The head failed for any other reason
+    Failure,
+}
+
+impl KillCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            KillCause::Collision => "collision",
+            KillCause::FellOff => "fell_off",
+            KillCause::Trapped => "trapped",
+            KillCause::Clash => "clash",
+            KillCause::Merged => "merged",
+            KillCause::Failure => "failure",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "collision" => Some(KillCause::Collision),
+            "fell_off" => Some(KillCause::FellOff),
+            "trapped" => Some(KillCause::Trapped),
+            "clash" => Some(KillCause::Clash),
+            "merged" => Some(KillCause::Merged),
+            "failure" => Some(KillCause::Failure),
+            _ => None,
+        }
+    }
+}
+
+// What happened on the board, sent to the observers as it happens
+#[derive(Debug, Clone, PartialEq)]
+pub enum BoardNotification {
+    HEAD_MOVED {
+        id: Id,
+        from: Coordinates,
+        to: Coordinates,
+    },
+    // A new head has been created on a separator
+    HEAD_SPLIT {
+        id: Id,
+        position: Coordinates,
+    },
+    HEAD_KILLED {
+        id: Id,
+        cause: KillCause,
+    },
+    // A head killed as merged goes on as part of another one
+    HEAD_MERGED {
+        id: Id,
+        into: Id,
+        // Volume of the head merged into
+        volume: u64,
+    },
+    // A head entered a goal tile and left the board
+    HEAD_DELIVERED {
+        id: Id,
+        position: Coordinates,
+        // Number of heads delivered at once, the ones merged into it included
+        volume: u64,
+    },
+    TILE_MARKED {
+        position: Coordinates,
+    },
+    // Several heads moved to the same tile. The losers are killed right after
+    HEADS_COLLIDED {
+        position: Coordinates,
+        rule: CollisionRule,
+        winner: Option<Id>,
+    },
+    ROW_SCROLLED {
+        slide_count: u64,
+    },
+    GAME_OVER(GameOver),
+    // Something failed on the board, for the frontend to tell the player
+    BOARD_FAILED(BoardError),
+}
+
+pub trait BoardObserver: Send {
+    fn notify(&mut self, notification: &BoardNotification);
+}
+
+// Forward the notifications to another thread. They are dropped once the receiver is gone
+impl BoardObserver for mpsc::Sender<BoardNotification> {
+    fn notify(&mut self, notification: &BoardNotification) {
+        let _ = self.send(notification.clone());
+    }
+}
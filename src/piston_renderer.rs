@@ -0,0 +1,166 @@
+use piston_window::{
+    clear, ellipse, rectangle, AdvancedWindow, Button, Event, Key, PistonWindow, PressEvent, RenderEvent, Transformed,
+    WindowSettings,
+};
+
+use crate::map::TileType;
+use crate::renderer::{FrontendEvent, Input, Menu, Renderer};
+use crate::snapshot::BoardSnapshot;
+use crate::utils::Direction;
+
+const TILE_SIZE: f64 = 32.0;
+const BACKGROUND_COLOR: [f32; 4] = [0.05, 0.05, 0.08, 1.0];
+const HEAD_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+const FOCUSED_HEAD_COLOR: [f32; 4] = [1.0, 0.85, 0.2, 1.0];
+const MENU_ENTRY_COLOR: [f32; 4] = [0.15, 0.15, 0.2, 1.0];
+const MENU_SELECTED_COLOR: [f32; 4] = [0.1, 0.45, 0.9, 1.0];
+
+fn tile_color(tile: TileType) -> [f32; 4] {
+    match tile {
+        TileType::Marked => [0.1, 0.45, 0.9, 1.0],
+        TileType::Free => [0.15, 0.15, 0.2, 1.0],
+        TileType::Separator => [0.95, 0.75, 0.1, 1.0],
+        TileType::Wall => [0.55, 0.55, 0.6, 1.0],
+        TileType::Goal => [0.2, 0.85, 0.35, 1.0],
+    }
+}
+
+// There is no font to write text with: menus are drawn as bars and the highlighted entry is
+// written in the window title, as are the messages shown during a game.
+pub struct PistonRenderer {
+    window: PistonWindow,
+    // Title the window was opened with
+    name: String,
+    title: String,
+    message: Option<String>,
+    // Render event waiting for the frame to be drawn
+    render_event: Option<Event>,
+}
+
+impl PistonRenderer {
+    pub fn new(title: &str, map_length: usize, map_height: usize) -> Result<Self, String> {
+        let size = [
+            (map_length as f64 * TILE_SIZE) as u32,
+            (map_height as f64 * TILE_SIZE) as u32,
+        ];
+        let window = WindowSettings::new(title, size)
+            .exit_on_esc(false)
+            .build()
+            .map_err(|error| format!("Cannot open window: {}", error))?;
+
+        Ok(PistonRenderer {
+            window,
+            name: title.to_string(),
+            title: title.to_string(),
+            message: None,
+            render_event: None,
+        })
+    }
+
+    fn set_title(&mut self, title: String) {
+        if title != self.title {
+            self.window.set_title(title.clone());
+            self.title = title;
+        }
+    }
+}
+
+impl Renderer for PistonRenderer {
+    fn next_event(&mut self) -> Option<FrontendEvent> {
+        loop {
+            let event = self.window.next()?;
+
+            if event.render_args().is_some() {
+                self.render_event = Some(event);
+                return Some(FrontendEvent::Render);
+            }
+
+            let input = match event.press_args() {
+                Some(Button::Keyboard(Key::Up)) => Input::SetDirection(Direction::Up),
+                Some(Button::Keyboard(Key::Down)) => Input::SetDirection(Direction::Down),
+                Some(Button::Keyboard(Key::Left)) => Input::SetDirection(Direction::Left),
+                Some(Button::Keyboard(Key::Right)) => Input::SetDirection(Direction::Right),
+                Some(Button::Keyboard(Key::Space)) => Input::ReleaseDirection,
+                Some(Button::Keyboard(Key::Return)) => Input::Confirm,
+                Some(Button::Keyboard(Key::Escape)) => Input::Back,
+                Some(Button::Keyboard(Key::P)) => Input::Pause,
+                Some(Button::Keyboard(Key::Tab)) => Input::CycleFocus,
+                Some(Button::Keyboard(Key::F5)) => Input::QuickSave,
+                Some(Button::Keyboard(Key::F9)) => Input::QuickLoad,
+                _ => continue,
+            };
+            return Some(FrontendEvent::Input(input));
+        }
+    }
+
+    fn draw(&mut self, snapshot: &BoardSnapshot, scroll: f64) {
+        let event = match self.render_event.take() {
+            Some(event) => event,
+            None => return,
+        };
+        let height = snapshot.tiles.len();
+        let length = snapshot.tiles.first().map_or(0, |row| row.len());
+        if height == 0 || length == 0 {
+            return;
+        }
+        let title = match &self.message {
+            Some(message) => format!("{} - {}", self.name, message),
+            None => self.name.clone(),
+        };
+        self.set_title(title);
+
+        self.window.draw_2d(&event, |context, graphics, _device| {
+            clear(BACKGROUND_COLOR, graphics);
+
+            // Tiles are scaled down when the map does not fit in the window
+            let [window_width, window_height] = context.get_view_size();
+            let tile_size = TILE_SIZE.min(window_width / length as f64).min(window_height / height as f64);
+
+            // Row 0 is at the bottom of the window, the whole map moves down as it slides
+            let transform = context.transform.trans(0.0, scroll * tile_size);
+            let tile_origin = |x: usize, y: usize| (x as f64 * tile_size, (height - 1 - y) as f64 * tile_size);
+
+            for (y, row) in snapshot.tiles.iter().enumerate() {
+                for (x, tile) in row.iter().enumerate() {
+                    let (left, top) = tile_origin(x, y);
+                    rectangle(tile_color(*tile), [left, top, tile_size, tile_size], transform, graphics);
+                }
+            }
+
+            for head in &snapshot.heads {
+                if head.position.y >= height {
+                    continue;
+                }
+                let (left, top) = tile_origin(head.position.x, head.position.y);
+                let margin = tile_size / 4.0;
+                let size = tile_size - 2.0 * margin;
+                let color = if snapshot.focused_head == Some(head.id) { FOCUSED_HEAD_COLOR } else { HEAD_COLOR };
+                ellipse(color, [left + margin, top + margin, size, size], transform, graphics);
+            }
+        });
+    }
+
+    fn draw_menu(&mut self, menu: &Menu) {
+        let event = match self.render_event.take() {
+            Some(event) => event,
+            None => return,
+        };
+        let selected_entry = menu.entries.get(menu.selected).map_or("", |entry| entry.as_str());
+        self.set_title(format!("{} - {}", menu.title, selected_entry));
+
+        self.window.draw_2d(&event, |context, graphics, _device| {
+            clear(BACKGROUND_COLOR, graphics);
+
+            let [window_width, _] = context.get_view_size();
+            for index in 0..menu.entries.len() {
+                let color = if index == menu.selected { MENU_SELECTED_COLOR } else { MENU_ENTRY_COLOR };
+                let top = TILE_SIZE * (1.0 + 1.5 * index as f64);
+                rectangle(color, [TILE_SIZE, top, window_width - 2.0 * TILE_SIZE, TILE_SIZE], context.transform, graphics);
+            }
+        });
+    }
+
+    fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+}
@@ -0,0 +1,40 @@
+use crate::snapshot::BoardSnapshot;
+use crate::utils::Direction;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Input {
+    SetDirection(Direction),
+    // Let the heads pick their direction by themselves again
+    ReleaseDirection,
+    Confirm,
+    Back,
+    Pause,
+    // Steer another head, or all of them again after the last one
+    CycleFocus,
+    QuickSave,
+    QuickLoad,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FrontendEvent {
+    // The frontend is ready to draw a new frame
+    Render,
+    Input(Input),
+}
+
+// List of choices, `selected` being the highlighted one
+pub struct Menu {
+    pub title: String,
+    pub entries: Vec<String>,
+    pub selected: usize,
+}
+
+pub trait Renderer {
+    // Block until the next frontend event. Returns `None` once the frontend has been closed
+    fn next_event(&mut self) -> Option<FrontendEvent>;
+    // `scroll` is the fraction of a tile the map has already slid towards its next frame
+    fn draw(&mut self, snapshot: &BoardSnapshot, scroll: f64);
+    fn draw_menu(&mut self, menu: &Menu);
+    // Message shown along with the board until another one replaces it, none clearing it
+    fn set_message(&mut self, message: Option<String>);
+}
@@ -0,0 +1,121 @@
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+
+use crate::board::BoardEvevents;
+use crate::level::HEADER_END;
+use crate::script::{self, ScriptError, ScriptHeader};
+
+// Replay files share the header of scripts. It is followed by every event the board received,
+// in the order it processed them, one per line as `<tick> <command>`. The tick is the number of
+// MOVE_HEADS_TICK and SLIDE_FRAME_TICK events received before the event.
+pub struct Recorder {
+    output: Box<dyn Write + Send>,
+    tick: u64,
+}
+
+impl Recorder {
+    pub fn new(mut output: Box<dyn Write + Send>, header: &ScriptHeader) -> io::Result<Self> {
+        writeln!(output, "{}{}", header, HEADER_END)?;
+        Ok(Recorder { output, tick: 0 })
+    }
+
+    pub fn record(&mut self, event: &BoardEvevents) -> io::Result<()> {
+        writeln!(self.output, "{} {}", self.tick, script::format_event(event))?;
+        if let BoardEvevents::MOVE_HEADS_TICK | BoardEvevents::SLIDE_FRAME_TICK = event {
+            self.tick += 1;
+        }
+        self.output.flush()
+    }
+}
+
+pub struct Replay {
+    pub header: ScriptHeader,
+    pub events: Vec<(u64, BoardEvevents)>,
+}
+
+impl Replay {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ScriptError> {
+        parse(&fs::read_to_string(path)?)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Replay, ScriptError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let header_end = lines
+        .iter()
+        .position(|line| line.trim_end() == HEADER_END)
+        .ok_or_else(|| ScriptError::syntax(1, "Replays start with a header closed by `---`"))?;
+    let header = script::parse_header(&lines[..header_end])?;
+
+    let mut events: Vec<(u64, BoardEvevents)> = Vec::new();
+    for (index, line) in lines.iter().enumerate().skip(header_end + 1) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        let syntax_error = |message: String| ScriptError::syntax(index + 1, message);
+
+        let (tick, command) = line
+            .split_once(' ')
+            .ok_or_else(|| syntax_error(String::from("Expected `<tick> <command>`")))?;
+        let tick: u64 = tick.parse().map_err(|_| syntax_error(format!("Invalid tick `{}`", tick)))?;
+        if events.last().is_some_and(|&(last_tick, _)| tick < last_tick) {
+            return Err(syntax_error(format!("Tick {} comes after a later one", tick)));
+        }
+        let (event, _) = script::parse_command(command.trim()).map_err(syntax_error)?;
+        events.push((tick, event));
+    }
+
+    Ok(Replay { header, events })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::direction_strategy::StrategyKind;
+    use crate::heads::Id;
+    use crate::notification::KillCause;
+    use crate::utils::Direction;
+
+    use super::*;
+
+    #[derive(Clone, Default)]
+    struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for SharedBuffer {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(bytes)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_record_then_parse() {
+        let header = ScriptHeader {
+            seed: Some(5),
+            map_size: Some((4, 6)),
+            strategy: Some(StrategyKind::Greedy(Direction::Up)),
+            ..ScriptHeader::default()
+        };
+        let buffer = SharedBuffer::default();
+        let mut recorder = Recorder::new(Box::new(buffer.clone()), &header).unwrap();
+        recorder.record(&BoardEvevents::MOVE_HEADS_TICK).unwrap();
+        recorder.record(&BoardEvevents::KILL_HEAD { id: Id { index: 1, generation: 3 }, cause: KillCause::Trapped }).unwrap();
+        recorder.record(&BoardEvevents::SLIDE_FRAME_TICK).unwrap();
+        recorder.record(&BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction: None }).unwrap();
+
+        let text = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert!(text.ends_with("---\n0 move\n1 kill 1.3 trapped\n1 slide\n2 dir none\n"), "{}", text);
+
+        let replay = parse(&text).unwrap();
+        assert_eq!(replay.header, header);
+        let ticks: Vec<u64> = replay.events.iter().map(|(tick, _)| *tick).collect();
+        assert_eq!(ticks, vec![0, 1, 1, 2]);
+        assert!(parse("---\n2 move\n1 move\n").is_err());
+    }
+}
@@ -0,0 +1,148 @@
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use crate::map::TileType;
+use crate::save::{GeneratorState, RngState};
+
+pub trait RowGenerator {
+    // Build the row that will be appended on top of `previous_row`
+    fn next_row(&mut self, previous_row: &[TileType]) -> Vec<TileType>;
+}
+
+pub struct SeededRowGenerator {
+    rng: ChaCha8Rng,
+    wall_density: f64,
+    separator_density: f64,
+    // Column of the previous row through which the guaranteed free path goes
+    passage: Option<usize>,
+}
+
+impl SeededRowGenerator {
+    pub const DEFAULT_WALL_DENSITY: f64 = 0.25;
+    pub const DEFAULT_SEPARATOR_DENSITY: f64 = 0.05;
+
+    pub fn new(seed: u64) -> Self {
+        Self::with_densities(seed, Self::DEFAULT_WALL_DENSITY, Self::DEFAULT_SEPARATOR_DENSITY)
+    }
+
+    pub fn with_densities(seed: u64, wall_density: f64, separator_density: f64) -> Self {
+        assert!(
+            wall_density >= 0.0 && separator_density >= 0.0 && wall_density + separator_density <= 1.0,
+            "Tile densities must be positive and sum up to at most 1"
+        );
+
+        SeededRowGenerator {
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            wall_density,
+            separator_density,
+            passage: None,
+        }
+    }
+
+    pub fn state(&self) -> GeneratorState {
+        GeneratorState {
+            rng: RngState::of(&self.rng),
+            passage: self.passage,
+        }
+    }
+
+    // Go on generating the rows from a saved state, with the current densities
+    pub fn restore(&mut self, state: GeneratorState) {
+        self.rng = state.rng.build();
+        self.passage = state.passage;
+    }
+
+    fn random_tile(&mut self) -> TileType {
+        let draw: f64 = self.rng.gen();
+        if draw < self.wall_density {
+            TileType::Wall
+        } else if draw < self.wall_density + self.separator_density {
+            TileType::Separator
+        } else {
+            TileType::Free
+        }
+    }
+}
+
+impl RowGenerator for SeededRowGenerator {
+    fn next_row(&mut self, previous_row: &[TileType]) -> Vec<TileType> {
+        let mut row: Vec<TileType> = (0..previous_row.len()).map(|_| self.random_tile()).collect();
+
+        // Start the free path from a column the previous row can be left through. Marked tiles are as
+        // closed as walls
+        let entry = match self.passage {
+            Some(passage) if passage < previous_row.len() => passage,
+            _ => {
+                let open_columns: Vec<usize> = (0..previous_row.len())
+                    .filter(|&x| matches!(previous_row[x], TileType::Free | TileType::Separator))
+                    .collect();
+                if open_columns.is_empty() {
+                    self.rng.gen_range(0..previous_row.len())
+                } else {
+                    open_columns[self.rng.gen_range(0..open_columns.len())]
+                }
+            }
+        };
+
+        // Carve the path vertically from the previous row, then horizontally to the next passage
+        let exit = self.rng.gen_range(0..row.len());
+        for tile in &mut row[entry.min(exit)..=entry.max(exit)] {
+            *tile = TileType::Free;
+        }
+        self.passage = Some(exit);
+
+        row
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn passable(tile: TileType) -> bool {
+        matches!(tile, TileType::Free | TileType::Separator)
+    }
+
+    #[test]
+    fn test_rows_stay_connected() {
+        let mut generator = SeededRowGenerator::with_densities(7, 0.8, 0.1);
+        let mut previous_row = vec![TileType::Wall, TileType::Free, TileType::Marked, TileType::Wall];
+        let mut reachable = vec![false, true, false, false];
+
+        for _ in 0..200 {
+            let row = generator.next_row(&previous_row);
+            assert_eq!(row.len(), previous_row.len());
+
+            // Enter the new row from the reachable tiles below, then spread along free tiles
+            let mut next_reachable: Vec<bool> = (0..row.len())
+                .map(|x| reachable[x] && passable(row[x]))
+                .collect();
+            for _ in 0..row.len() {
+                for x in 0..row.len() {
+                    let from_left = x > 0 && next_reachable[x - 1];
+                    let from_right = x + 1 < row.len() && next_reachable[x + 1];
+                    if passable(row[x]) && (from_left || from_right) {
+                        next_reachable[x] = true;
+                    }
+                }
+            }
+            assert!(next_reachable.contains(&true), "Row {:?} is not reachable from {:?}", row, previous_row);
+
+            previous_row = row;
+            reachable = next_reachable;
+        }
+    }
+
+    #[test]
+    fn test_same_seed_same_rows() {
+        let mut first = SeededRowGenerator::new(42);
+        let mut second = SeededRowGenerator::new(42);
+        let mut row = vec![TileType::Free; 6];
+
+        for _ in 0..20 {
+            let next_row = first.next_row(&row);
+            assert_eq!(next_row, second.next_row(&row));
+            row = next_row;
+        }
+    }
+}
@@ -0,0 +1,469 @@
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use crate::board::BoardState;
+use crate::collision::TrailMark;
+use crate::game_over::GameOverReason;
+use crate::heads::{HeadState, Id};
+use crate::level::{self, HeaderEntry, LevelError, LevelHeader, HEADER_END};
+use crate::map::{Map, TileType};
+use crate::notification::KillCause;
+use crate::score::Score;
+use crate::utils::{Coordinates, Direction};
+
+// Saved games are levels whose header holds the whole state of the board on top of the level keys:
+//   seed: <seed>                  seed the game was started with
+//   slide_count: <count>
+//   tick_count: <count>
+//   next_direction: <direction|none>
+//   focus: <id|none>              head steered by the player
+//   steer: <id> <direction>       direction given to a head of its own, one line per head
+//   state: running | over <reason>
+//   score: <marked tiles> <splits> <head ticks> <rows scrolled> <combo bonus> <combo streak> <delivered heads>
+//   rng: <rng>                    generator of the seeds given to new heads
+//   generator: <rng> <passage|none>   row generator of the map
+//   births: <count>               birth rank of the next head
+//   slots: <generation>...|none   generation of each head slot, from the first one on
+//   free_slots: <index>...|none   empty head slots, the last one being reused first
+//   head: <id> <birth> <lineage> <volume> <marked tiles> <x>,<y> <coming from> <moving|splitting|dying:<cause>|delivered> <rng>
+//   mark: <x>,<y> <id> <tick>     tile lately marked by a head, one line per tile
+// where <rng> is the ChaCha seed in hexadecimal followed by its word position.
+// The grid is the map as it is when the game is saved.
+
+// Exact state of a random number generator, as long as it runs on the default stream
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RngState {
+    pub seed: [u8; 32],
+    pub word_pos: u128,
+}
+
+impl RngState {
+    pub fn of(rng: &ChaCha8Rng) -> Self {
+        RngState {
+            seed: rng.get_seed(),
+            word_pos: rng.get_word_pos(),
+        }
+    }
+
+    pub fn build(&self) -> ChaCha8Rng {
+        let mut rng = ChaCha8Rng::from_seed(self.seed);
+        rng.set_word_pos(self.word_pos);
+        rng
+    }
+
+    fn parse(seed: &str, word_pos: &str) -> Option<Self> {
+        if seed.len() != 64 || !seed.is_ascii() {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        for (index, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&seed[2 * index..2 * index + 2], 16).ok()?;
+        }
+        Some(RngState {
+            seed: bytes,
+            word_pos: word_pos.parse().ok()?,
+        })
+    }
+}
+
+impl fmt::Display for RngState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.seed {
+            write!(f, "{:02x}", byte)?;
+        }
+        write!(f, " {}", self.word_pos)
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct GeneratorState {
+    pub rng: RngState,
+    // Column the next generated row is entered from
+    pub passage: Option<usize>,
+}
+
+// Maps that can hand their rows to come over to a save file and pick them up again after a load
+pub trait SavableMap: Map {
+    fn generator_state(&self) -> GeneratorState;
+    // Replace the whole map, rows to come included. Saved games are read with the checks of the levels,
+    // their rows are never empty and all as long
+    fn restore(&mut self, rows: VecDeque<Vec<TileType>>, generator: GeneratorState);
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeadSave {
+    pub id: Id,
+    pub birth: u64,
+    pub lineage: u64,
+    pub volume: u64,
+    pub marked_tiles: u64,
+    pub position: Coordinates,
+    pub coming_from: Direction,
+    pub state: HeadState,
+    pub picker: RngState,
+}
+
+// Slots of the head list, so that the heads to come get the ids and births they would have
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct HeadListSave {
+    pub generations: Vec<u32>,
+    pub free_slots: Vec<u32>,
+    pub births: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SavedGame {
+    // Level keys still in use during the game: the strategy, the collision and trail rules and the end conditions
+    pub header: LevelHeader,
+    // Rows from the bottom of the map to its top
+    pub rows: VecDeque<Vec<TileType>>,
+    pub seed: u64,
+    pub slide_count: u64,
+    pub tick_count: u64,
+    pub next_direction: Option<Direction>,
+    pub focused_head: Option<Id>,
+    pub head_directions: BTreeMap<Id, Direction>,
+    pub state: BoardState,
+    pub score: Score,
+    pub rng: RngState,
+    pub generator: GeneratorState,
+    pub head_list: HeadListSave,
+    pub heads: Vec<HeadSave>,
+    pub marks: Vec<TrailMark>,
+}
+
+impl SavedGame {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LevelError> {
+        parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn to_file(&self, path: impl AsRef<Path>) -> Result<(), LevelError> {
+        Ok(fs::write(path, self.to_string())?)
+    }
+}
+
+impl fmt::Display for SavedGame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.header)?;
+        writeln!(f, "seed: {}", self.seed)?;
+        writeln!(f, "slide_count: {}", self.slide_count)?;
+        writeln!(f, "tick_count: {}", self.tick_count)?;
+        writeln!(f, "next_direction: {}", self.next_direction.map_or("none", |direction| direction.name()))?;
+        match self.focused_head {
+            Some(id) => writeln!(f, "focus: {}", id)?,
+            None => writeln!(f, "focus: none")?,
+        }
+        for (id, direction) in &self.head_directions {
+            writeln!(f, "steer: {} {}", id, direction.name())?;
+        }
+        match self.state {
+            BoardState::Running => writeln!(f, "state: running")?,
+            BoardState::Over(reason) => writeln!(f, "state: over {}", reason.name())?,
+        }
+
+        let score = &self.score;
+        writeln!(
+            f,
+            "score: {} {} {} {} {} {} {}",
+            score.marked_tiles,
+            score.splits,
+            score.head_ticks,
+            score.rows_scrolled,
+            score.combo_bonus,
+            score.combo_streak,
+            score.delivered_heads
+        )?;
+        writeln!(f, "rng: {}", self.rng)?;
+        match self.generator.passage {
+            Some(passage) => writeln!(f, "generator: {} {}", self.generator.rng, passage)?,
+            None => writeln!(f, "generator: {} none", self.generator.rng)?,
+        }
+        writeln!(f, "births: {}", self.head_list.births)?;
+        writeln!(f, "slots: {}", format_slots(&self.head_list.generations))?;
+        writeln!(f, "free_slots: {}", format_slots(&self.head_list.free_slots))?;
+        for head in &self.heads {
+            let state = match head.state {
+                HeadState::Dying(cause) => format!("dying:{}", cause.name()),
+                HeadState::Splitting => String::from("splitting"),
+                HeadState::Delivered => String::from("delivered"),
+                HeadState::Alive | HeadState::Moving => String::from("moving"),
+            };
+            writeln!(
+                f,
+                "head: {} {} {} {} {} {},{} {} {} {}",
+                head.id,
+                head.birth,
+                head.lineage,
+                head.volume,
+                head.marked_tiles,
+                head.position.x,
+                head.position.y,
+                head.coming_from.name(),
+                state,
+                head.picker
+            )?;
+        }
+        for mark in &self.marks {
+            writeln!(f, "mark: {},{} {} {}", mark.position.x, mark.position.y, mark.id, mark.tick)?;
+        }
+
+        writeln!(f, "{}", HEADER_END)?;
+        write!(f, "{}", level::format_grid(&self.rows))
+    }
+}
+
+pub fn parse(text: &str) -> Result<SavedGame, LevelError> {
+    let lines: Vec<&str> = text.lines().collect();
+    let header_end = lines
+        .iter()
+        .position(|line| line.trim_end() == HEADER_END)
+        .ok_or_else(|| LevelError::Syntax { line: 1, column: 1, message: String::from("A saved game needs a header") })?;
+
+    let mut saved = SavedGame {
+        header: LevelHeader::default(),
+        rows: level::parse_grid(&lines, header_end + 1)?,
+        seed: 0,
+        slide_count: 0,
+        tick_count: 0,
+        next_direction: None,
+        focused_head: None,
+        head_directions: BTreeMap::new(),
+        state: BoardState::Running,
+        score: Score::default(),
+        rng: RngState { seed: [0; 32], word_pos: 0 },
+        generator: GeneratorState { rng: RngState { seed: [0; 32], word_pos: 0 }, passage: None },
+        head_list: HeadListSave::default(),
+        heads: Vec::new(),
+        marks: Vec::new(),
+    };
+    let mut rng = None;
+    let mut generator = None;
+    let mut births = None;
+    let mut generations = None;
+    let mut free_slots = None;
+
+    for (index, line) in lines[..header_end].iter().enumerate() {
+        let entry = match level::split_header_line(line, index + 1)? {
+            Some(entry) => entry,
+            None => continue,
+        };
+        if level::parse_header_entry(&mut saved.header, &entry)? {
+            continue;
+        }
+
+        let words: Vec<&str> = entry.value.split_whitespace().collect();
+        let count = || level::parse_count(entry.value, entry.line, entry.value_column);
+        match entry.key {
+            "seed" => saved.seed = count()?,
+            "slide_count" => saved.slide_count = count()?,
+            "tick_count" => saved.tick_count = count()?,
+            "next_direction" => {
+                saved.next_direction = match entry.value {
+                    "none" => None,
+                    name => Some(parse_direction(&entry, name)?),
+                }
+            }
+            "focus" => {
+                saved.focused_head = match entry.value {
+                    "none" => None,
+                    id => Some(parse_id(&entry, id)?),
+                }
+            }
+            "steer" => match words[..] {
+                [id, direction] => {
+                    saved.head_directions.insert(parse_id(&entry, id)?, parse_direction(&entry, direction)?);
+                }
+                _ => return Err(entry.invalid_value("Expected `<id> <direction>`")),
+            },
+            "state" => {
+                saved.state = match words[..] {
+                    ["running"] => BoardState::Running,
+                    ["over", reason] => BoardState::Over(
+                        GameOverReason::from_name(reason)
+                            .ok_or_else(|| entry.invalid_value(format!("Unknown game over reason `{}`", reason)))?,
+                    ),
+                    _ => return Err(entry.invalid_value("Expected `running` or `over <reason>`")),
+                }
+            }
+            "score" => {
+                let counters: Vec<u64> = words.iter().filter_map(|word| word.parse().ok()).collect();
+                match counters[..] {
+                    [marked_tiles, splits, head_ticks, rows_scrolled, combo_bonus, combo_streak, delivered_heads] if words.len() == 7 => {
+                        saved.score = Score { marked_tiles, splits, head_ticks, rows_scrolled, combo_bonus, combo_streak, delivered_heads }
+                    }
+                    _ => return Err(entry.invalid_value("Expected the seven score counters")),
+                }
+            }
+            "rng" => match words[..] {
+                [seed, word_pos] => rng = Some(parse_rng(&entry, seed, word_pos)?),
+                _ => return Err(entry.invalid_value("Expected `<seed> <word position>`")),
+            },
+            "generator" => match words[..] {
+                [seed, word_pos, passage] => {
+                    let passage = match passage {
+                        "none" => None,
+                        passage => Some(passage.parse().map_err(|_| entry.invalid_value(format!("Invalid passage `{}`", passage)))?),
+                    };
+                    generator = Some(GeneratorState { rng: parse_rng(&entry, seed, word_pos)?, passage })
+                }
+                _ => return Err(entry.invalid_value("Expected `<seed> <word position> <passage|none>`")),
+            },
+            "births" => births = Some(count()?),
+            "slots" => generations = Some(parse_slots(&entry, &words)?),
+            "free_slots" => free_slots = Some(parse_slots(&entry, &words)?),
+            "head" => saved.heads.push(parse_head(&entry, &words, &saved.rows)?),
+            "mark" => match words[..] {
+                [position, id, tick] => saved.marks.push(TrailMark {
+                    position: level::parse_coordinates(position, entry.line, entry.value_column)?,
+                    id: parse_id(&entry, id)?,
+                    tick: level::parse_count(tick, entry.line, entry.value_column)?,
+                }),
+                _ => return Err(entry.invalid_value("Expected `<x>,<y> <id> <tick>`")),
+            },
+            _ => return Err(entry.unknown_key()),
+        }
+    }
+
+    // Without them, the game would not go on as it would have
+    let missing = |key: &str| LevelError::Syntax { line: header_end + 1, column: 1, message: format!("Missing `{}` header key", key) };
+    saved.rng = rng.ok_or_else(|| missing("rng"))?;
+    saved.generator = generator.ok_or_else(|| missing("generator"))?;
+    saved.head_list = HeadListSave {
+        generations: generations.ok_or_else(|| missing("slots"))?,
+        free_slots: free_slots.ok_or_else(|| missing("free_slots"))?,
+        births: births.ok_or_else(|| missing("births"))?,
+    };
+    let slot_count = saved.head_list.generations.len() as u32;
+    if let Some(index) = saved.head_list.free_slots.iter().find(|&&index| index >= slot_count) {
+        let message = format!("Free slot {} is out of the {} head slots", index, slot_count);
+        return Err(LevelError::Syntax { line: header_end + 1, column: 1, message });
+    }
+    Ok(saved)
+}
+
+fn format_slots(slots: &[u32]) -> String {
+    match slots {
+        [] => String::from("none"),
+        slots => slots.iter().map(|slot| slot.to_string()).collect::<Vec<_>>().join(" "),
+    }
+}
+
+fn parse_slots(entry: &HeaderEntry, words: &[&str]) -> Result<Vec<u32>, LevelError> {
+    match words {
+        ["none"] => Ok(Vec::new()),
+        words => words
+            .iter()
+            .map(|word| word.parse().map_err(|_| entry.invalid_value(format!("Invalid slot `{}`", word))))
+            .collect(),
+    }
+}
+
+fn parse_direction(entry: &HeaderEntry, name: &str) -> Result<Direction, LevelError> {
+    Direction::from_name(name).ok_or_else(|| entry.invalid_value(format!("Unknown direction `{}`", name)))
+}
+
+fn parse_id(entry: &HeaderEntry, id: &str) -> Result<Id, LevelError> {
+    id.parse().map_err(|_| entry.invalid_value(format!("Invalid head id `{}`", id)))
+}
+
+fn parse_rng(entry: &HeaderEntry, seed: &str, word_pos: &str) -> Result<RngState, LevelError> {
+    RngState::parse(seed, word_pos).ok_or_else(|| entry.invalid_value(format!("Invalid generator state `{} {}`", seed, word_pos)))
+}
+
+fn parse_head(entry: &HeaderEntry, words: &[&str], rows: &VecDeque<Vec<TileType>>) -> Result<HeadSave, LevelError> {
+    let &[id, birth, lineage, volume, marked_tiles, position, coming_from, state, seed, word_pos] = words else {
+        return Err(entry.invalid_value(
+            "Expected `<id> <birth> <lineage> <volume> <marked tiles> <x>,<y> <coming from> <state> <seed> <word position>`",
+        ));
+    };
+    let count = |value: &str| level::parse_count(value, entry.line, entry.value_column);
+
+    let id = parse_id(entry, id)?;
+    let birth = count(birth)?;
+    let position = level::parse_coordinates(position, entry.line, entry.value_column)?;
+    if position.y >= rows.len() || position.x >= rows[0].len() {
+        return Err(entry.invalid_value(format!("Head {} is out of the map", id)));
+    }
+    let state = match state.split_once(':') {
+        None if state == "moving" => HeadState::Moving,
+        None if state == "splitting" => HeadState::Splitting,
+        None if state == "delivered" => HeadState::Delivered,
+        Some(("dying", cause)) => {
+            HeadState::Dying(KillCause::from_name(cause).ok_or_else(|| entry.invalid_value(format!("Unknown kill cause `{}`", cause)))?)
+        }
+        _ => return Err(entry.invalid_value(format!("Unknown head state `{}`", state))),
+    };
+
+    Ok(HeadSave {
+        id,
+        birth,
+        lineage: count(lineage)?,
+        volume: count(volume)?,
+        marked_tiles: count(marked_tiles)?,
+        position,
+        coming_from: parse_direction(entry, coming_from)?,
+        state,
+        picker: parse_rng(entry, seed, word_pos)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::Rng;
+
+    use crate::direction_strategy::StrategyKind;
+
+    use super::*;
+
+    #[test]
+    fn test_rng_state_roundtrip() {
+        let mut rng = ChaCha8Rng::seed_from_u64(12);
+        for _ in 0..5 {
+            rng.gen::<u64>();
+        }
+
+        let text = RngState::of(&rng).to_string();
+        let (seed, word_pos) = text.split_once(' ').unwrap();
+        let mut restored = RngState::parse(seed, word_pos).unwrap().build();
+        assert_eq!(restored.gen::<u64>(), rng.gen::<u64>());
+    }
+
+    #[test]
+    fn test_saved_game_roundtrip() {
+        let rng = RngState::of(&ChaCha8Rng::seed_from_u64(1));
+        let saved = SavedGame {
+            header: LevelHeader {
+                strategy: Some(StrategyKind::Uniform),
+                ..LevelHeader::default()
+            },
+            rows: VecDeque::from([vec![TileType::Marked, TileType::Free], vec![TileType::Separator, TileType::Wall]]),
+            seed: 4,
+            slide_count: 2,
+            tick_count: 9,
+            next_direction: Some(Direction::Left),
+            focused_head: Some(Id { index: 2, generation: 1 }),
+            head_directions: BTreeMap::from([(Id { index: 0, generation: 0 }, Direction::Up)]),
+            state: BoardState::Over(GameOverReason::GoalReached),
+            score: Score { marked_tiles: 3, splits: 1, head_ticks: 9, rows_scrolled: 2, combo_bonus: 5, combo_streak: 1, delivered_heads: 2 },
+            rng,
+            generator: GeneratorState { rng, passage: Some(1) },
+            head_list: HeadListSave { generations: vec![0, 0, 1], free_slots: vec![1], births: 4 },
+            heads: vec![
+                HeadSave { id: Id { index: 0, generation: 0 }, birth: 0, lineage: 0, volume: 2, marked_tiles: 7, position: Coordinates { x: 0, y: 0 }, coming_from: Direction::Down, state: HeadState::Splitting, picker: rng },
+                HeadSave { id: Id { index: 2, generation: 1 }, birth: 3, lineage: 3, volume: 1, marked_tiles: 0, position: Coordinates { x: 1, y: 0 }, coming_from: Direction::Left, state: HeadState::Dying(KillCause::FellOff), picker: rng },
+            ],
+            marks: vec![TrailMark { position: Coordinates { x: 0, y: 1 }, id: Id { index: 0, generation: 0 }, tick: 8 }],
+        };
+
+        assert_eq!(parse(&saved.to_string()).unwrap(), saved);
+
+        // Heads are placed on the map
+        let text = saved.to_string().replace("head: 2.1 3 3 1 0 1,0", "head: 2.1 3 3 1 0 1,2");
+        assert!(matches!(parse(&text), Err(LevelError::Syntax { line: 16, .. })));
+    }
+}
@@ -0,0 +1,107 @@
+use std::fmt;
+
+// Progress of a game. Each counter is worth a fixed number of points, plus a combo bonus
+// growing with the number of heads kept alive together and how long they stay so.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Score {
+    pub marked_tiles: u64,
+    pub splits: u64,
+    // Sum over the move ticks of the number of heads alive
+    pub head_ticks: u64,
+    pub rows_scrolled: u64,
+    pub combo_bonus: u64,
+    // Consecutive move ticks with several heads alive
+    pub combo_streak: u64,
+    // Heads that entered a goal tile, merged heads counting for their whole volume
+    pub delivered_heads: u64,
+}
+
+impl Score {
+    pub const MARKED_TILE_POINTS: u64 = 10;
+    pub const SPLIT_POINTS: u64 = 50;
+    pub const HEAD_TICK_POINTS: u64 = 1;
+    pub const ROW_SCROLLED_POINTS: u64 = 25;
+    pub const COMBO_POINTS: u64 = 5;
+    pub const DELIVERED_HEAD_POINTS: u64 = 100;
+    // The combo stops growing after that many ticks in a row
+    pub const MAX_COMBO_STREAK: u64 = 10;
+
+    pub fn tile_marked(&mut self) {
+        self.marked_tiles += 1;
+    }
+
+    pub fn head_split(&mut self) {
+        self.splits += 1;
+    }
+
+    pub fn row_scrolled(&mut self) {
+        self.rows_scrolled += 1;
+    }
+
+    pub fn heads_delivered(&mut self, volume: u64) {
+        self.delivered_heads += volume;
+    }
+
+    pub fn move_tick(&mut self, heads_alive: usize) {
+        let heads_alive = heads_alive as u64;
+        self.head_ticks += heads_alive;
+
+        if heads_alive > 1 {
+            self.combo_streak = (self.combo_streak + 1).min(Self::MAX_COMBO_STREAK);
+            self.combo_bonus += (heads_alive - 1) * self.combo_streak * Self::COMBO_POINTS;
+        } else {
+            self.combo_streak = 0;
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.marked_tiles * Self::MARKED_TILE_POINTS
+            + self.splits * Self::SPLIT_POINTS
+            + self.head_ticks * Self::HEAD_TICK_POINTS
+            + self.rows_scrolled * Self::ROW_SCROLLED_POINTS
+            + self.combo_bonus
+            + self.delivered_heads * Self::DELIVERED_HEAD_POINTS
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Marked tiles: {}", self.marked_tiles)?;
+        writeln!(f, "Splits: {}", self.splits)?;
+        writeln!(f, "Head ticks: {}", self.head_ticks)?;
+        writeln!(f, "Rows scrolled: {}", self.rows_scrolled)?;
+        writeln!(f, "Combo bonus: {}", self.combo_bonus)?;
+        writeln!(f, "Delivered heads: {}", self.delivered_heads)?;
+        writeln!(f, "Score: {}", self.total())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_combo_bonus() {
+        let mut score = Score::default();
+
+        score.move_tick(1);
+        assert_eq!(score.combo_bonus, 0);
+        score.move_tick(3);
+        score.move_tick(2);
+        assert_eq!(score.combo_bonus, 2 * Score::COMBO_POINTS + 2 * Score::COMBO_POINTS);
+
+        // Losing all heads but one breaks the streak
+        score.move_tick(1);
+        score.move_tick(2);
+        assert_eq!(score.combo_bonus, 5 * Score::COMBO_POINTS);
+        assert_eq!(score.head_ticks, 9);
+
+        score.tile_marked();
+        score.head_split();
+        score.row_scrolled();
+        assert_eq!(
+            score.total(),
+            Score::MARKED_TILE_POINTS + Score::SPLIT_POINTS + 9 * Score::HEAD_TICK_POINTS + Score::ROW_SCROLLED_POINTS + 5 * Score::COMBO_POINTS
+        );
+    }
+}
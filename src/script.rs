@@ -0,0 +1,287 @@
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::board::BoardEvevents;
+use crate::collision::CollisionRule;
+use crate::direction_strategy::StrategyKind;
+use crate::level::HEADER_END;
+use crate::map;
+use crate::notification::KillCause;
+use crate::utils::{Coordinates, Direction};
+
+// Scripts drive a board without any timer or frontend. Like levels, they start with an optional
+// header of `key: value` lines closed by a `---` line, followed by one command per line:
+//   move [count]        MOVE_HEADS_TICK
+//   slide [count]       SLIDE_FRAME_TICK
+//   dir <direction>     SET_NEXT_HEAD_DIRECTION, `none` releases the direction
+//   add <x>,<y> <coming from> <parent direction>   ADD_HEAD
+//   kill <id> [cause]   KILL_HEAD, killed by a collision unless another cause is given among
+//                       collision, fell_off, trapped, clash, merged and failure
+//   focus <id|none>     FOCUS_HEAD
+//   focus next          FOCUS_NEXT_HEAD
+//   save <file>         SAVE_GAME
+//   load <file>         LOAD_GAME
+// Lines starting with `;` are comments.
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ScriptHeader {
+    pub seed: Option<u64>,
+    // Path of the level, relative to the script file
+    pub level: Option<String>,
+    pub map_size: Option<(usize, usize)>,
+    pub strategy: Option<StrategyKind>,
+    pub collision: Option<CollisionRule>,
+}
+
+// Header lines, without the closing `---`
+impl fmt::Display for ScriptHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(seed) = self.seed {
+            writeln!(f, "seed: {}", seed)?;
+        }
+        if let Some(level) = &self.level {
+            writeln!(f, "level: {}", level)?;
+        }
+        if let Some((width, height)) = self.map_size {
+            writeln!(f, "size: {}x{}", width, height)?;
+        }
+        if let Some(strategy) = self.strategy {
+            writeln!(f, "strategy: {}", strategy)?;
+        }
+        if let Some(collision) = self.collision {
+            writeln!(f, "collision: {}", collision)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Script {
+    pub header: ScriptHeader,
+    pub events: Vec<BoardEvevents>,
+}
+
+impl Script {
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ScriptError> {
+        parse(&fs::read_to_string(path)?)
+    }
+}
+
+#[derive(Debug)]
+pub enum ScriptError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+}
+
+impl ScriptError {
+    pub fn syntax(line: usize, message: impl Into<String>) -> Self {
+        ScriptError::Syntax {
+            line,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScriptError::Io(error) => write!(f, "Cannot read script: {}", error),
+            ScriptError::Syntax { line, message } => write!(f, "Line {}: {}", line, message),
+        }
+    }
+}
+
+impl From<io::Error> for ScriptError {
+    fn from(error: io::Error) -> Self {
+        ScriptError::Io(error)
+    }
+}
+
+pub fn parse(text: &str) -> Result<Script, ScriptError> {
+    let lines: Vec<&str> = text.lines().collect();
+
+    let (header, commands_start) = match lines.iter().position(|line| line.trim_end() == HEADER_END) {
+        Some(header_end) => (parse_header(&lines[..header_end])?, header_end + 1),
+        None => (ScriptHeader::default(), 0),
+    };
+
+    let mut events = Vec::new();
+    for (index, line) in lines.iter().enumerate().skip(commands_start) {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+        let (event, count) = parse_command(line).map_err(|message| ScriptError::syntax(index + 1, message))?;
+        events.extend(std::iter::repeat_n(event, count));
+    }
+
+    Ok(Script { header, events })
+}
+
+// Parse a command line into the event it stands for and the number of times it is repeated
+pub fn parse_command(line: &str) -> Result<(BoardEvevents, usize), String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let parse_count = |words: &[&str]| match words {
+        [] => Ok(1),
+        [count] => count.parse::<usize>().map_err(|_| format!("Invalid count `{}`", count)),
+        _ => Err(format!("Too many arguments in `{}`", line)),
+    };
+
+    match words[0] {
+        "move" => Ok((BoardEvevents::MOVE_HEADS_TICK, parse_count(&words[1..])?)),
+        "slide" => Ok((BoardEvevents::SLIDE_FRAME_TICK, parse_count(&words[1..])?)),
+        "dir" => {
+            let direction = match words[1..] {
+                ["none"] => None,
+                [name] => Some(Direction::from_name(name).ok_or(format!("Unknown direction `{}`", name))?),
+                _ => return Err(String::from("Expected `dir <up|down|left|right|none>`")),
+            };
+            Ok((BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction }, 1))
+        }
+        "add" => {
+            let parse_direction = |name: &str| Direction::from_name(name).ok_or(format!("Unknown direction `{}`", name));
+            match words[1..] {
+                [position, coming_from, parent_direction] => {
+                    let position = parse_position(position)?;
+                    let coming_from = parse_direction(coming_from)?;
+                    let parent_direction = parse_direction(parent_direction)?;
+                    Ok((BoardEvevents::ADD_HEAD { position, coming_from, parent_direction }, 1))
+                }
+                _ => Err(String::from("Expected `add <x>,<y> <coming from> <parent direction>`")),
+            }
+        }
+        "kill" => {
+            let parse_id = |id: &str| id.parse().map_err(|_| format!("Invalid head id `{}`", id));
+            let (id, cause) = match words[1..] {
+                [id] => (parse_id(id)?, KillCause::Collision),
+                [id, cause] => (parse_id(id)?, KillCause::from_name(cause).ok_or(format!("Unknown kill cause `{}`", cause))?),
+                _ => return Err(String::from("Expected `kill <id> [collision|fell_off|trapped|clash|merged|failure]`")),
+            };
+            Ok((BoardEvevents::KILL_HEAD { id, cause }, 1))
+        }
+        "focus" => match words[1..] {
+            ["next"] => Ok((BoardEvevents::FOCUS_NEXT_HEAD, 1)),
+            ["none"] => Ok((BoardEvevents::FOCUS_HEAD { id: None }, 1)),
+            [id] => Ok((BoardEvevents::FOCUS_HEAD { id: Some(id.parse().map_err(|_| format!("Invalid head id `{}`", id))?) }, 1)),
+            _ => Err(String::from("Expected `focus <id|none|next>`")),
+        },
+        "save" => match words[1..] {
+            [path] => Ok((BoardEvevents::SAVE_GAME { path: path.to_string() }, 1)),
+            _ => Err(String::from("Expected `save <file>`")),
+        },
+        "load" => match words[1..] {
+            [path] => Ok((BoardEvevents::LOAD_GAME { path: path.to_string() }, 1)),
+            _ => Err(String::from("Expected `load <file>`")),
+        },
+        other => Err(format!("Unknown command `{}`", other)),
+    }
+}
+
+fn parse_position(text: &str) -> Result<Coordinates, String> {
+    let parse_value = |text: &str| text.parse::<usize>().ok();
+    match text.split_once(',').map(|(x, y)| (parse_value(x), parse_value(y))) {
+        Some((Some(x), Some(y))) => Ok(Coordinates { x, y }),
+        _ => Err(format!("Invalid position `{}`, expected <x>,<y>", text)),
+    }
+}
+
+// Command line standing for a single event, as read by `parse_command`
+pub fn format_event(event: &BoardEvevents) -> String {
+    match event {
+        BoardEvevents::MOVE_HEADS_TICK => String::from("move"),
+        BoardEvevents::SLIDE_FRAME_TICK => String::from("slide"),
+        BoardEvevents::SET_NEXT_HEAD_DIRECTION { direction } => {
+            format!("dir {}", direction.map_or("none", |direction| direction.name()))
+        }
+        BoardEvevents::ADD_HEAD { position, coming_from, parent_direction } => format!(
+            "add {},{} {} {}",
+            position.x,
+            position.y,
+            coming_from.name(),
+            parent_direction.name()
+        ),
+        BoardEvevents::KILL_HEAD { id, cause } => format!("kill {} {}", id, cause.name()),
+        BoardEvevents::FOCUS_HEAD { id: Some(id) } => format!("focus {}", id),
+        BoardEvevents::FOCUS_HEAD { id: None } => String::from("focus none"),
+        BoardEvevents::FOCUS_NEXT_HEAD => String::from("focus next"),
+        BoardEvevents::SAVE_GAME { path } => format!("save {}", path),
+        BoardEvevents::LOAD_GAME { path } => format!("load {}", path),
+    }
+}
+
+pub fn parse_header(lines: &[&str]) -> Result<ScriptHeader, ScriptError> {
+    let mut header = ScriptHeader::default();
+
+    for (index, line) in lines.iter().enumerate() {
+        let line_number = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with(';') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once(':')
+            .ok_or_else(|| ScriptError::syntax(line_number, "Expected a `key: value` pair"))?;
+        let value = value.trim();
+        let syntax_error = |message: String| ScriptError::syntax(line_number, message);
+
+        match key.trim() {
+            "seed" => header.seed = Some(value.parse().map_err(|_| syntax_error(format!("Invalid seed `{}`", value)))?),
+            "level" => header.level = Some(value.to_string()),
+            "size" => header.map_size = Some(map::parse_size(value).map_err(syntax_error)?),
+            "strategy" => header.strategy = Some(value.parse().map_err(syntax_error)?),
+            "collision" => header.collision = Some(value.parse().map_err(syntax_error)?),
+            other => return Err(syntax_error(format!("Unknown header key `{}`", other))),
+        }
+    }
+
+    Ok(header)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_script() {
+        let script = parse("seed: 12\nsize: 5x8\n---\n; warm up\nmove 2\ndir left\nslide\ndir none\n").unwrap();
+
+        assert_eq!(script.header.seed, Some(12));
+        assert_eq!(script.header.map_size, Some((5, 8)));
+        assert_eq!(script.header.level, None);
+        let events: Vec<String> = script.events.iter().map(|event| format!("{:?}", event)).collect();
+        assert_eq!(
+            events,
+            vec![
+                "MOVE_HEADS_TICK",
+                "MOVE_HEADS_TICK",
+                "SET_NEXT_HEAD_DIRECTION { direction: Some(Left) }",
+                "SLIDE_FRAME_TICK",
+                "SET_NEXT_HEAD_DIRECTION { direction: None }",
+            ]
+        );
+
+        let error_line = |text: &str| match parse(text) {
+            Err(ScriptError::Syntax { line, .. }) => line,
+            _ => panic!("Expected a syntax error for {:?}", text),
+        };
+        assert_eq!(error_line("move\njump\n"), 2);
+        assert_eq!(error_line("seed: -1\n---\nmove\n"), 1);
+        assert_eq!(error_line("---\nmove 1 2\n"), 2);
+        assert_eq!(error_line("---\ndir sideways\n"), 2);
+        assert_eq!(error_line("---\nmove\nadd 1;2 up down\n"), 3);
+    }
+
+    #[test]
+    fn test_format_event() {
+        for line in ["move", "slide", "dir up", "dir none", "add 3,14 down left", "kill 2.1 fell_off", "focus 1.0", "focus none", "focus next", "save quick.sav", "load quick.sav"] {
+            let (event, _) = parse_command(line).unwrap();
+            assert_eq!(format_event(&event), line);
+        }
+
+        // Kills without a cause come from older scripts
+        let (event, _) = parse_command("kill 2").unwrap();
+        assert_eq!(format_event(&event), "kill 2.0 collision");
+    }
+}
@@ -0,0 +1,59 @@
+use std::fmt;
+use std::sync::mpsc;
+
+use crate::game_over::GameOver;
+use crate::heads::Id;
+use crate::map::TileType;
+use crate::score::Score;
+use crate::utils::{Coordinates, Direction};
+
+// Copy of the board state that frontends can read without touching the board itself
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BoardSnapshot {
+    // Rows from the bottom of the map to its top
+    pub tiles: Vec<Vec<TileType>>,
+    pub heads: Vec<HeadSnapshot>,
+    // Head the player is steering, if any
+    pub focused_head: Option<Id>,
+    // Number of times the map slid since the start of the game
+    pub slide_count: u64,
+    // Number of move ticks since the start of the game
+    pub tick_count: u64,
+    pub score: Score,
+    pub game_over: Option<GameOver>,
+}
+
+pub const HEAD_CHAR: char = '@';
+
+impl BoardSnapshot {
+    pub fn head_at(&self, position: Coordinates) -> Option<&HeadSnapshot> {
+        self.heads.iter().find(|head| head.position == position)
+    }
+}
+
+// Plain text view of the board, top row first, with the level file characters
+impl fmt::Display for BoardSnapshot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (y, row) in self.tiles.iter().enumerate().rev() {
+            for (x, tile) in row.iter().enumerate() {
+                match self.head_at(Coordinates { x, y }) {
+                    Some(_) => write!(f, "{}", HEAD_CHAR)?,
+                    None => write!(f, "{}", tile.to_char())?,
+                }
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HeadSnapshot {
+    pub id: Id,
+    pub position: Coordinates,
+    // Side of the tile the head entered it from
+    pub coming_from: Direction,
+}
+
+// Outbound end of the channel the board publishes its snapshots on
+pub type SnapshotSender = mpsc::Sender<BoardSnapshot>;
@@ -1,8 +1,229 @@
-//use crate::Signals;
+// Hierarchical state machines.
+//
+// States are plain values, usually enum variants, arranged in a tree through `parent`. The machine
+// always rests in a leaf state. An event is first offered to the current state; a state that does
+// not handle it returns `Response::Super` to pass it on to its parent. A transition exits every
+// state up to the closest common ancestor of the source and the target, enters every state down to
+// the target, then follows the `initial` children of the target until a leaf is reached.
 
-pub trait StateMachine {}
+// Shape of the state tree and actions run when entering or leaving states
+pub trait Hierarchy {
+    type State: Copy + PartialEq;
+    type Error;
 
-impl dyn StateMachine {
-    pub fn init() {}
-    pub fn dispatch() {}
+    fn state(&self) -> Self::State;
+    fn set_state(&mut self, state: Self::State);
+    fn parent(state: Self::State) -> Option<Self::State>;
+
+    // Child entered along with a composite state, `None` for leaf states
+    fn initial(_state: Self::State) -> Option<Self::State> {
+        None
+    }
+
+    fn entry(&mut self, _state: Self::State) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    fn exit(&mut self, _state: Self::State) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    // Transitions refused by the guard leave the machine in its current state
+    fn guard(&self, _source: Self::State, _target: Self::State) -> bool {
+        true
+    }
+
+    // Enter the current state and its ancestors, from the top of the tree
+    fn init(&mut self) -> Result<(), Self::Error> {
+        let mut path = ancestors::<Self>(self.state());
+        path.reverse();
+        for state in path {
+            self.entry(state)?;
+        }
+        self.enter_initial(self.state())
+    }
+
+    fn transition(&mut self, target: Self::State) -> Result<(), Self::Error> {
+        let source = self.state();
+        if !self.guard(source, target) {
+            return Ok(());
+        }
+
+        // A transition to the source itself or to one of its ancestors leaves and enters the target again
+        let target_path = ancestors::<Self>(target);
+        let mut exited = Some(source);
+        while let Some(state) = exited {
+            if state != target && target_path.contains(&state) {
+                break;
+            }
+            self.exit(state)?;
+            exited = Self::parent(state);
+        }
+
+        let entered_count = target_path.iter().take_while(|&&state| Some(state) != exited).count();
+        for &state in target_path[..entered_count].iter().rev() {
+            self.entry(state)?;
+        }
+        self.enter_initial(target)
+    }
+
+    fn enter_initial(&mut self, mut state: Self::State) -> Result<(), Self::Error> {
+        while let Some(child) = Self::initial(state) {
+            self.entry(child)?;
+            state = child;
+        }
+        self.set_state(state);
+        Ok(())
+    }
+}
+
+pub enum Response<State, Output> {
+    Handled(Output),
+    // Let the parent state handle the event
+    Super,
+    Transition(State, Output),
+}
+
+pub trait StateMachine<Event>: Hierarchy {
+    type Output: Default;
+
+    fn handle(&mut self, state: Self::State, event: &mut Event) -> Result<Response<Self::State, Self::Output>, Self::Error>;
+
+    // Offer `event` to the current state, then to its ancestors until one of them handles it.
+    // Events handled by no state are dropped with the default output.
+    fn process(&mut self, mut event: Event) -> Result<Self::Output, Self::Error> {
+        let mut state = Some(self.state());
+        while let Some(handling_state) = state {
+            match self.handle(handling_state, &mut event)? {
+                Response::Handled(output) => return Ok(output),
+                Response::Super => state = Self::parent(handling_state),
+                Response::Transition(target, output) => {
+                    self.transition(target)?;
+                    return Ok(output);
+                }
+            }
+        }
+        Ok(Self::Output::default())
+    }
+}
+
+// `state` followed by its parent, up to the top of the tree
+fn ancestors<Machine: Hierarchy + ?Sized>(state: Machine::State) -> Vec<Machine::State> {
+    let mut path = vec![state];
+    while let Some(parent) = Machine::parent(path[path.len() - 1]) {
+        path.push(parent);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Traffic light that can be switched off, its colours are nested in its running state
+    #[derive(Debug, Copy, Clone, PartialEq)]
+    enum Light {
+        On,
+        Red,
+        Green,
+        Off,
+    }
+
+    enum Signal {
+        Next,
+        Switch,
+    }
+
+    struct TrafficLight {
+        state: Light,
+        log: Vec<String>,
+        broken: bool,
+    }
+
+    impl Hierarchy for TrafficLight {
+        type State = Light;
+        type Error = ();
+
+        fn state(&self) -> Light {
+            self.state
+        }
+
+        fn set_state(&mut self, state: Light) {
+            self.state = state;
+        }
+
+        fn parent(state: Light) -> Option<Light> {
+            match state {
+                Light::Red | Light::Green => Some(Light::On),
+                Light::On | Light::Off => None,
+            }
+        }
+
+        fn initial(state: Light) -> Option<Light> {
+            match state {
+                Light::On => Some(Light::Red),
+                _ => None,
+            }
+        }
+
+        fn entry(&mut self, state: Light) -> Result<(), ()> {
+            self.log.push(format!("enter {:?}", state));
+            Ok(())
+        }
+
+        fn exit(&mut self, state: Light) -> Result<(), ()> {
+            self.log.push(format!("exit {:?}", state));
+            Ok(())
+        }
+
+        fn guard(&self, _source: Light, target: Light) -> bool {
+            !(self.broken && target == Light::On)
+        }
+    }
+
+    impl StateMachine<Signal> for TrafficLight {
+        type Output = bool;
+
+        fn handle(&mut self, state: Light, event: &mut Signal) -> Result<Response<Light, bool>, ()> {
+            let response = match (state, event) {
+                (Light::Red, Signal::Next) => Response::Transition(Light::Green, true),
+                (Light::Green, Signal::Next) => Response::Transition(Light::Red, true),
+                (Light::On, Signal::Switch) => Response::Transition(Light::Off, true),
+                (Light::Off, Signal::Switch) => Response::Transition(Light::On, true),
+                _ => Response::Super,
+            };
+            Ok(response)
+        }
+    }
+
+    #[test]
+    fn test_nested_transitions() {
+        let mut light = TrafficLight { state: Light::On, log: Vec::new(), broken: false };
+        light.init().unwrap();
+        assert_eq!(light.state, Light::Red);
+
+        assert_eq!(light.process(Signal::Next), Ok(true));
+        assert_eq!(light.state, Light::Green);
+
+        // Handled by the parent state, leaving the child first
+        light.process(Signal::Switch).unwrap();
+        assert_eq!(light.state, Light::Off);
+        assert_eq!(light.process(Signal::Next), Ok(false));
+
+        light.process(Signal::Switch).unwrap();
+        assert_eq!(light.state, Light::Red);
+        assert_eq!(
+            light.log,
+            vec![
+                "enter On", "enter Red", "exit Red", "enter Green", "exit Green", "exit On", "enter Off", "exit Off",
+                "enter On", "enter Red",
+            ]
+        );
+
+        // The guard keeps the light off
+        light.process(Signal::Switch).unwrap();
+        light.broken = true;
+        light.process(Signal::Switch).unwrap();
+        assert_eq!(light.state, Light::Off);
+    }
 }
@@ -0,0 +1,195 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::mem::MaybeUninit;
+use std::time::{Duration, Instant};
+
+use crate::map::TileType;
+use crate::renderer::{FrontendEvent, Input, Menu, Renderer};
+use crate::snapshot::{BoardSnapshot, HEAD_CHAR};
+use crate::utils::{Coordinates, Direction};
+
+const FRAME_PERIOD: Duration = Duration::from_millis(50);
+const CTRL_C: u8 = 0x03;
+
+fn tile_color(tile: TileType) -> &'static str {
+    match tile {
+        TileType::Marked => "\x1b[1;34m",
+        TileType::Free => "\x1b[2;37m",
+        TileType::Separator => "\x1b[1;33m",
+        TileType::Wall => "\x1b[0;37m",
+        TileType::Goal => "\x1b[1;32m",
+    }
+}
+
+// Draws the board with ANSI escape codes and reads arrow keys from a raw mode terminal.
+// The map slides a whole row at a time, `scroll` is not rendered.
+pub struct TuiRenderer {
+    original_termios: libc::termios,
+    inputs: VecDeque<Input>,
+    next_frame: Instant,
+    closed: bool,
+    message: Option<String>,
+}
+
+impl TuiRenderer {
+    pub fn new() -> Result<Self, String> {
+        let original_termios = unsafe {
+            let mut termios = MaybeUninit::<libc::termios>::uninit();
+            if libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) != 0 {
+                return Err(format!("Cannot read terminal attributes: {}", io::Error::last_os_error()));
+            }
+            termios.assume_init()
+        };
+
+        let mut raw_termios = original_termios;
+        unsafe {
+            libc::cfmakeraw(&mut raw_termios);
+            if libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw_termios) != 0 {
+                return Err(format!("Cannot switch terminal to raw mode: {}", io::Error::last_os_error()));
+            }
+        }
+
+        // Hide the cursor and clear the screen
+        print!("\x1b[?25l\x1b[2J");
+
+        Ok(TuiRenderer {
+            original_termios,
+            inputs: VecDeque::new(),
+            next_frame: Instant::now(),
+            closed: false,
+            message: None,
+        })
+    }
+
+    // Wait at most `timeout` for the terminal to have bytes to read
+    fn wait_for_input(timeout: Duration) -> bool {
+        let mut poll_fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let timeout_ms = timeout.as_millis().min(i32::MAX as u128) as i32;
+        unsafe { libc::poll(&mut poll_fd, 1, timeout_ms) > 0 }
+    }
+
+    fn print_frame(frame: &str) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(frame.as_bytes());
+        let _ = stdout.flush();
+    }
+
+    fn read_inputs(&mut self) {
+        let mut buffer = [0u8; 64];
+        let count = match io::stdin().read(&mut buffer) {
+            Ok(0) | Err(_) => {
+                self.closed = true;
+                return;
+            }
+            Ok(count) => count,
+        };
+
+        let mut bytes = buffer[..count].iter().copied().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                // Arrow keys are sent as `ESC [ A` to `ESC [ D`
+                0x1b if bytes.peek() == Some(&b'[') => {
+                    bytes.next();
+                    let direction = match bytes.next() {
+                        Some(b'A') => Direction::Up,
+                        Some(b'B') => Direction::Down,
+                        Some(b'C') => Direction::Right,
+                        Some(b'D') => Direction::Left,
+                        _ => continue,
+                    };
+                    self.inputs.push_back(Input::SetDirection(direction));
+                }
+                b'q' | CTRL_C => self.closed = true,
+                0x1b => self.inputs.push_back(Input::Back),
+                b'\r' | b'\n' => self.inputs.push_back(Input::Confirm),
+                b'p' => self.inputs.push_back(Input::Pause),
+                b'\t' => self.inputs.push_back(Input::CycleFocus),
+                b's' => self.inputs.push_back(Input::QuickSave),
+                b'l' => self.inputs.push_back(Input::QuickLoad),
+                b' ' => self.inputs.push_back(Input::ReleaseDirection),
+                _ => (),
+            }
+        }
+    }
+}
+
+impl Drop for TuiRenderer {
+    fn drop(&mut self) {
+        unsafe {
+            libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &self.original_termios);
+        }
+        // Show the cursor again and leave the board on screen
+        print!("\x1b[0m\x1b[?25h\r\n");
+        let _ = io::stdout().flush();
+    }
+}
+
+impl Renderer for TuiRenderer {
+    fn next_event(&mut self) -> Option<FrontendEvent> {
+        loop {
+            if self.closed {
+                return None;
+            }
+            if let Some(input) = self.inputs.pop_front() {
+                return Some(FrontendEvent::Input(input));
+            }
+
+            let now = Instant::now();
+            if now >= self.next_frame {
+                self.next_frame = now + FRAME_PERIOD;
+                return Some(FrontendEvent::Render);
+            }
+
+            if Self::wait_for_input(self.next_frame - now) {
+                self.read_inputs();
+            }
+        }
+    }
+
+    fn draw(&mut self, snapshot: &BoardSnapshot, _scroll: f64) {
+        let mut frame = String::from("\x1b[H");
+
+        // Row 0 is printed last, at the bottom of the screen
+        for (y, row) in snapshot.tiles.iter().enumerate().rev() {
+            for (x, tile) in row.iter().enumerate() {
+                if let Some(head) = snapshot.head_at(Coordinates { x, y }) {
+                    // The focused head is yellow
+                    let focused = snapshot.focused_head == Some(head.id);
+                    frame.push_str(if focused { "\x1b[1;93m" } else { "\x1b[1;97m" });
+                    frame.push(HEAD_CHAR);
+                } else {
+                    frame.push_str(tile_color(*tile));
+                    frame.push(tile.to_char());
+                }
+            }
+            frame.push_str("\x1b[0m\x1b[K\r\n");
+        }
+        frame.push_str(&format!("Score: {}  Arrows: steer  Space: release  Tab: focus  p: pause  s/l: save/load  q: quit\x1b[K", snapshot.score.total()));
+        if let Some(message) = &self.message {
+            frame.push_str(&format!("\r\n\x1b[1;31m{}\x1b[0m\x1b[K", message));
+        }
+        frame.push_str("\x1b[J");
+        Self::print_frame(&frame);
+    }
+
+    fn draw_menu(&mut self, menu: &Menu) {
+        let mut frame = format!("\x1b[H\x1b[1m{}\x1b[0m\x1b[K\r\n\x1b[K\r\n", menu.title);
+        for (index, entry) in menu.entries.iter().enumerate() {
+            if index == menu.selected {
+                frame.push_str(&format!("\x1b[1;97m> {}\x1b[0m\x1b[K\r\n", entry));
+            } else {
+                frame.push_str(&format!("  {}\x1b[K\r\n", entry));
+            }
+        }
+        frame.push_str("\x1b[K\r\nArrows: move  Enter: select  Esc: back  q: quit\x1b[K\x1b[J");
+        Self::print_frame(&frame);
+    }
+
+    fn set_message(&mut self, message: Option<String>) {
+        self.message = message;
+    }
+}
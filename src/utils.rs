@@ -18,6 +18,39 @@ pub fn reverse(&self) -> Direction {
         Direction::Left => Direction::Right
     }
 }
+
+// Directions are seen from above the map, `Up` going towards higher rows
+pub fn turn_left(&self) -> Direction {
+    match self {
+        Direction::Up => Direction::Left,
+        Direction::Left => Direction::Down,
+        Direction::Down => Direction::Right,
+        Direction::Right => Direction::Up
+    }
+}
+
+pub fn turn_right(&self) -> Direction {
+    self.turn_left().reverse()
+}
+
+pub fn from_name(name: &str) -> Option<Direction> {
+    match name {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "right" => Some(Direction::Right),
+        "left" => Some(Direction::Left),
+        _ => None
+    }
+}
+
+pub fn name(&self) -> &'static str {
+    match self {
+        Direction::Up => "up",
+        Direction::Down => "down",
+        Direction::Right => "right",
+        Direction::Left => "left"
+    }
+}
 }
 pub type DirectionFlags = BitFlags<Direction>;
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]